@@ -0,0 +1,378 @@
+use super::coexpression::Profile;
+use super::{CoexpressionOptions, CorrelationMethod, TPMValue};
+use serde::{Deserialize, Serialize};
+
+/// How the distance between two clusters is computed from the distances of their tissues.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Linkage {
+    /// Mean distance between the tissues of the two clusters (UPGMA).
+    #[default]
+    Average,
+    /// Largest distance between the tissues of the two clusters.
+    Complete,
+    /// Increase in within-cluster variance caused by the merge.
+    Ward,
+}
+
+/// What the values of a `TissueMatrix` measure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MatrixKind {
+    /// Correlations, from -1 to 1; similar tissues have high values.
+    Correlation,
+    /// Distances, 0 on the diagonal; similar tissues have low values.
+    Distance,
+}
+
+/// Symmetric tissue × tissue matrix, of correlations or distances, in the tissue order of the
+/// summary metadata.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TissueMatrix {
+    tissue_names: Vec<String>,
+    kind: MatrixKind,
+    /// `values[a * tissue_names.len() + b]`
+    values: Vec<f32>,
+}
+
+impl TissueMatrix {
+    /// Correlates the columns of a TPM matrix, each holding the values of one tissue across
+    /// the same genes.
+    ///
+    /// Tissues with the same value for every gene have a correlation of 0 with the others.
+    pub(crate) fn correlations(
+        tissue_names: Vec<String>,
+        columns: &[Vec<TPMValue>],
+        method: CorrelationMethod,
+        log_transform: bool,
+    ) -> Self {
+        let options = CoexpressionOptions {
+            method,
+            log_transform,
+            ..CoexpressionOptions::default()
+        };
+        let profiles: Vec<Option<Profile>> = columns
+            .iter()
+            .map(|column| Profile::new(column, &options))
+            .collect();
+
+        let num_tissues = tissue_names.len();
+        let mut values = vec![0.0; num_tissues * num_tissues];
+        for a in 0..num_tissues {
+            values[a * num_tissues + a] = 1.0;
+            for b in a + 1..num_tissues {
+                if let (Some(profile_a), Some(profile_b)) = (&profiles[a], &profiles[b]) {
+                    let correlation = profile_a.correlation(profile_b);
+                    values[a * num_tissues + b] = correlation;
+                    values[b * num_tissues + a] = correlation;
+                }
+            }
+        }
+        Self {
+            tissue_names,
+            kind: MatrixKind::Correlation,
+            values,
+        }
+    }
+
+    /// Returns the tissue names, in row and column order.
+    pub fn tissue_names(&self) -> &[String] {
+        &self.tissue_names
+    }
+
+    /// Returns whether the matrix holds correlations or distances.
+    pub fn kind(&self) -> MatrixKind {
+        self.kind
+    }
+
+    /// Returns the value of the tissues at positions `a` and `b`.
+    pub fn get(&self, a: usize, b: usize) -> f32 {
+        self.values[a * self.tissue_names.len() + b]
+    }
+
+    /// Returns the value of two tissues by name.
+    pub fn get_by_name(&self, a: &str, b: &str) -> Option<f32> {
+        let position = |name: &str| self.tissue_names.iter().position(|tissue| tissue == name);
+        Some(self.get(position(a)?, position(b)?))
+    }
+
+    /// Converts a correlation matrix into the correlation distances `1 - r`; a distance matrix
+    /// is returned unchanged.
+    pub fn to_distances(&self) -> TissueMatrix {
+        let values = match self.kind {
+            MatrixKind::Correlation => self.values.iter().map(|r| 1.0 - r).collect(),
+            MatrixKind::Distance => self.values.clone(),
+        };
+        TissueMatrix {
+            tissue_names: self.tissue_names.clone(),
+            kind: MatrixKind::Distance,
+            values,
+        }
+    }
+
+    /// Clusters the tissues by repeatedly merging the two closest clusters.
+    ///
+    /// A correlation matrix is first converted to the distances `1 - r` (see `to_distances`),
+    /// so the most correlated tissues are merged first. Distances between a merged cluster and
+    /// the others follow the Lance–Williams updates of `linkage`; ties are broken by tissue
+    /// order.
+    pub fn cluster(&self, linkage: Linkage) -> Dendrogram {
+        let matrix = self.to_distances();
+        let num_tissues = self.tissue_names.len();
+        // Distances between the active clusters, indexed by node
+        let mut distances: Vec<Vec<f32>> = (0..num_tissues)
+            .map(|a| (0..num_tissues).map(|b| matrix.get(a, b)).collect())
+            .collect();
+        let mut active: Vec<usize> = (0..num_tissues).collect();
+        let mut sizes = vec![1; num_tissues];
+        let mut merges = Vec::with_capacity(num_tissues.saturating_sub(1));
+
+        while active.len() > 1 {
+            let mut closest = (0, 1);
+            for i in 0..active.len() {
+                for j in i + 1..active.len() {
+                    let (ci, cj) = (active[closest.0], active[closest.1]);
+                    if distances[active[i]][active[j]] < distances[ci][cj] {
+                        closest = (i, j);
+                    }
+                }
+            }
+            let (left, right) = (active[closest.0], active[closest.1]);
+            let height = distances[left][right];
+            let node = num_tissues + merges.len();
+            let size = sizes[left] + sizes[right];
+
+            let mut row = vec![0.0; node + 1];
+            for &other in &active {
+                if other != left && other != right {
+                    let distance = linkage.update(
+                        distances[other][left],
+                        distances[other][right],
+                        height,
+                        (sizes[left], sizes[right], sizes[other]),
+                    );
+                    row[other] = distance;
+                }
+            }
+            for (other, column) in distances.iter_mut().enumerate() {
+                column.push(row[other]);
+            }
+            distances.push(row);
+            sizes.push(size);
+
+            merges.push(Merge {
+                left,
+                right,
+                height,
+                size,
+            });
+            active.retain(|&cluster| cluster != left && cluster != right);
+            active.push(node);
+        }
+
+        Dendrogram {
+            tissue_names: self.tissue_names.clone(),
+            merges,
+        }
+    }
+}
+
+impl Linkage {
+    // Distance between cluster k and the union of clusters i and j
+    fn update(
+        &self,
+        d_ki: f32,
+        d_kj: f32,
+        d_ij: f32,
+        (n_i, n_j, n_k): (usize, usize, usize),
+    ) -> f32 {
+        let (n_i, n_j, n_k) = (n_i as f32, n_j as f32, n_k as f32);
+        match self {
+            Linkage::Average => (n_i * d_ki + n_j * d_kj) / (n_i + n_j),
+            Linkage::Complete => d_ki.max(d_kj),
+            Linkage::Ward => (((n_k + n_i) * d_ki.powi(2) + (n_k + n_j) * d_kj.powi(2)
+                - n_k * d_ij.powi(2))
+                / (n_i + n_j + n_k))
+                .max(0.0)
+                .sqrt(),
+        }
+    }
+}
+
+/// One merge of an agglomerative clustering.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Merge {
+    /// Merged nodes: tissue positions below the number of tissues, otherwise the cluster
+    /// formed by merge `node - num_tissues`.
+    pub left: usize,
+    pub right: usize,
+    /// Distance between the two nodes when they were merged.
+    pub height: f32,
+    /// Number of tissues in the merged cluster.
+    pub size: usize,
+}
+
+/// Result of `TissueMatrix::cluster`, with the merges in the order they were made.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Dendrogram {
+    tissue_names: Vec<String>,
+    merges: Vec<Merge>,
+}
+
+impl Dendrogram {
+    pub fn tissue_names(&self) -> &[String] {
+        &self.tissue_names
+    }
+
+    pub fn merges(&self) -> &[Merge] {
+        &self.merges
+    }
+
+    /// Returns the tree in Newick format, with branch lengths equal to the difference in
+    /// merge height between a node and its parent.
+    ///
+    /// Tissue names with characters reserved by the format are quoted.
+    pub fn to_newick(&self) -> String {
+        let mut newick = String::new();
+        match self.merges.len() {
+            0 if self.tissue_names.is_empty() => {}
+            0 => newick.push_str(&newick_label(&self.tissue_names[0])),
+            num_merges => self.write_node(self.tissue_names.len() + num_merges - 1, &mut newick),
+        }
+        newick.push(';');
+        newick
+    }
+
+    fn height(&self, node: usize) -> f32 {
+        node.checked_sub(self.tissue_names.len())
+            .map_or(0.0, |merge| self.merges[merge].height)
+    }
+
+    fn write_node(&self, node: usize, newick: &mut String) {
+        let Some(merge) = node
+            .checked_sub(self.tissue_names.len())
+            .map(|merge| &self.merges[merge])
+        else {
+            newick.push_str(&newick_label(&self.tissue_names[node]));
+            return;
+        };
+        newick.push('(');
+        for (i, child) in [merge.left, merge.right].into_iter().enumerate() {
+            if i > 0 {
+                newick.push(',');
+            }
+            self.write_node(child, newick);
+            newick.push_str(&format!(":{}", merge.height - self.height(child)));
+        }
+        newick.push(')');
+    }
+}
+
+fn newick_label(name: &str) -> String {
+    if name.contains(|c: char| c.is_whitespace() || "()[]':;,".contains(c)) {
+        format!("'{}'", name.replace('\'', "''"))
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distances(values: &[f32]) -> TissueMatrix {
+        TissueMatrix {
+            tissue_names: ["A", "B", "C", "D"].map(String::from).to_vec(),
+            kind: MatrixKind::Distance,
+            values: values.to_vec(),
+        }
+    }
+
+    // Two pairs, (A, B) and (C, D), far from each other
+    const PAIRS: [f32; 16] = [
+        0.0, 2.0, 6.0, 10.0, //
+        2.0, 0.0, 5.0, 9.0, //
+        6.0, 5.0, 0.0, 4.0, //
+        10.0, 9.0, 4.0, 0.0,
+    ];
+
+    #[test]
+    fn test_correlations() {
+        let matrix = TissueMatrix::correlations(
+            vec!["Liver".to_string(), "Lung".to_string(), "Flat".to_string()],
+            &[
+                vec![1.0, 2.0, 3.0],
+                vec![2.0, 4.0, 6.0],
+                vec![1.0, 1.0, 1.0],
+            ],
+            CorrelationMethod::Pearson,
+            false,
+        );
+        assert!((matrix.get_by_name("Liver", "Lung").unwrap() - 1.0).abs() < 1e-6);
+        assert_eq!(matrix.get(0, 2), 0.0);
+        assert_eq!(matrix.get(2, 2), 1.0);
+        assert_eq!(matrix.kind(), MatrixKind::Correlation);
+        let distances = matrix.to_distances();
+        assert_eq!(distances.kind(), MatrixKind::Distance);
+        assert!(distances.get(0, 1).abs() < 1e-6);
+        assert_eq!(distances.to_distances(), distances);
+    }
+
+    #[test]
+    fn test_cluster_correlations_as_distances() {
+        let matrix = TissueMatrix::correlations(
+            ["A", "B", "C"].map(String::from).to_vec(),
+            &[
+                vec![1.0, 2.0, 3.0, 4.0],
+                vec![1.0, 2.0, 3.0, 5.0],
+                vec![4.0, 1.0, 3.0, 1.0],
+            ],
+            CorrelationMethod::Pearson,
+            false,
+        );
+        let dendrogram = matrix.cluster(Linkage::Average);
+        // The two correlated tissues are merged first
+        assert_eq!(
+            (dendrogram.merges()[0].left, dendrogram.merges()[0].right),
+            (0, 1)
+        );
+        assert_eq!(dendrogram, matrix.to_distances().cluster(Linkage::Average));
+    }
+
+    #[test]
+    fn test_linkages() {
+        let matrix = distances(&PAIRS);
+        let heights = |linkage| -> Vec<f32> {
+            matrix
+                .cluster(linkage)
+                .merges()
+                .iter()
+                .map(|merge| merge.height)
+                .collect()
+        };
+        // Average: d((A,B),(C,D)) = (6 + 10 + 5 + 9) / 4
+        assert_eq!(heights(Linkage::Average), [2.0, 4.0, 7.5]);
+        assert_eq!(heights(Linkage::Complete), [2.0, 4.0, 10.0]);
+        let ward = heights(Linkage::Ward);
+        assert_eq!(ward[..2], [2.0, 4.0]);
+        assert!(ward[2] > 7.5);
+
+        let dendrogram = matrix.cluster(Linkage::Average);
+        assert_eq!(dendrogram.merges()[0].size, 2);
+        assert_eq!(dendrogram.merges()[2].size, 4);
+    }
+
+    #[test]
+    fn test_newick() {
+        let dendrogram = distances(&PAIRS).cluster(Linkage::Average);
+        assert_eq!(dendrogram.to_newick(), "((A:2,B:2):5.5,(C:4,D:4):3.5);");
+
+        let single = TissueMatrix {
+            tissue_names: vec!["Brain - Cortex".to_string()],
+            kind: MatrixKind::Distance,
+            values: vec![0.0],
+        };
+        assert_eq!(
+            single.cluster(Linkage::Ward).to_newick(),
+            "'Brain - Cortex';"
+        );
+    }
+}
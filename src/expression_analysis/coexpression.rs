@@ -0,0 +1,130 @@
+use super::differential::mid_ranks;
+use super::{DGEResult, TPMValue};
+use serde::{Deserialize, Serialize};
+
+/// Correlation coefficient used to compare the tissue profiles of two genes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CorrelationMethod {
+    /// Linear correlation of the values.
+    #[default]
+    Pearson,
+    /// Correlation of the ranks of the values across tissues, robust to outlier tissues.
+    Spearman,
+}
+
+/// Settings of a co-expression query.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CoexpressionOptions {
+    pub method: CorrelationMethod,
+    /// Correlates log2(TPM + 1) rather than TPM, so that a few very high tissues do not
+    /// dominate a Pearson correlation.
+    pub log_transform: bool,
+    /// Number of neighbours returned for a query gene.
+    pub top_k: usize,
+    /// Minimum absolute correlation of an edge in the all-pairs mode.
+    pub min_abs_correlation: f32,
+}
+
+impl Default for CoexpressionOptions {
+    fn default() -> Self {
+        Self {
+            method: CorrelationMethod::Pearson,
+            log_transform: true,
+            top_k: 20,
+            min_abs_correlation: 0.8,
+        }
+    }
+}
+
+/// A gene co-expressed with a query gene.
+#[derive(Debug, Clone)]
+pub struct CoexpressedGene<'a> {
+    pub gene: &'a DGEResult,
+    pub correlation: f32,
+}
+
+/// A pair of co-expressed genes of the all-pairs mode.
+#[derive(Debug, Clone)]
+pub struct CoexpressionEdge<'a> {
+    pub gene_a: &'a DGEResult,
+    pub gene_b: &'a DGEResult,
+    pub correlation: f32,
+}
+
+/// Tissue profile of a gene, centred and scaled to unit length so that the correlation of two
+/// profiles is their dot product.
+#[derive(Debug)]
+pub(crate) struct Profile(Vec<f64>);
+
+impl Profile {
+    /// Returns `None` for a constant profile, whose correlation is undefined.
+    pub(crate) fn new(tpms: &[TPMValue], options: &CoexpressionOptions) -> Option<Self> {
+        let mut values: Vec<f64> = tpms
+            .iter()
+            .map(|&tpm| {
+                if options.log_transform {
+                    (tpm as f64 + 1.0).log2()
+                } else {
+                    tpm as f64
+                }
+            })
+            .collect();
+        if options.method == CorrelationMethod::Spearman {
+            values = mid_ranks(&values).0;
+        }
+
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        values.iter_mut().for_each(|value| *value -= mean);
+        let norm = values.iter().map(|value| value * value).sum::<f64>().sqrt();
+        if norm == 0.0 || norm.is_nan() {
+            return None;
+        }
+        values.iter_mut().for_each(|value| *value /= norm);
+        Some(Self(values))
+    }
+
+    pub(crate) fn correlation(&self, other: &Profile) -> f32 {
+        let dot: f64 = self.0.iter().zip(&other.0).map(|(a, b)| a * b).sum();
+        dot.clamp(-1.0, 1.0) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(method: CorrelationMethod, log_transform: bool) -> CoexpressionOptions {
+        CoexpressionOptions {
+            method,
+            log_transform,
+            ..CoexpressionOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_pearson_and_spearman() {
+        let pearson = options(CorrelationMethod::Pearson, false);
+        let a = Profile::new(&[1.0, 2.0, 3.0, 4.0], &pearson).unwrap();
+        let b = Profile::new(&[2.0, 4.0, 6.0, 8.0], &pearson).unwrap();
+        let c = Profile::new(&[4.0, 3.0, 2.0, 1.0], &pearson).unwrap();
+        assert!((a.correlation(&b) - 1.0).abs() < 1e-6);
+        assert!((a.correlation(&c) + 1.0).abs() < 1e-6);
+
+        // r = 11 / sqrt(5 * 29) for x = 1..4 and y = [1, 2, 3, 8]
+        let d = Profile::new(&[1.0, 2.0, 3.0, 8.0], &pearson).unwrap();
+        assert!((a.correlation(&d) - 0.913_500).abs() < 1e-5);
+
+        // Monotonic profiles have a rank correlation of one
+        let spearman = options(CorrelationMethod::Spearman, false);
+        let a = Profile::new(&[1.0, 2.0, 3.0, 4.0], &spearman).unwrap();
+        let d = Profile::new(&[1.0, 2.0, 3.0, 800.0], &spearman).unwrap();
+        assert!((a.correlation(&d) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_constant_profile() {
+        let options = CoexpressionOptions::default();
+        assert!(Profile::new(&[5.0, 5.0, 5.0], &options).is_none());
+        assert!(Profile::new(&[0.0, 0.0, 1.0], &options).is_some());
+    }
+}
@@ -0,0 +1,100 @@
+use super::scoring::mean_sd;
+use super::{DGEResult, Regulation, TPMValue, ZScoreValue};
+use serde::{Deserialize, Serialize};
+
+/// Thresholds classifying the genes of a comparison between two groups of tissues.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ComparisonThresholds {
+    /// Minimum absolute log2 fold change of a call.
+    pub min_log2_fold_change: f32,
+    /// Minimum absolute z-statistic of a call, when it is defined.
+    pub min_z_score: ZScoreValue,
+}
+
+impl Default for ComparisonThresholds {
+    fn default() -> Self {
+        Self {
+            min_log2_fold_change: 1.0,
+            min_z_score: 2.0,
+        }
+    }
+}
+
+/// Difference in expression of one gene between two groups of tissues.
+#[derive(Debug, Clone)]
+pub struct GeneComparison<'a> {
+    pub gene: &'a DGEResult,
+    /// Mean log2(TPM + 1) of the first group minus that of the second.
+    pub log2_fold_change: f32,
+    /// Difference of the group means over its standard error, `None` if neither group has
+    /// any spread (e.g. a single tissue against another).
+    pub z_score: Option<ZScoreValue>,
+    /// Direction of the difference, if it passes the thresholds. Without a z-statistic the
+    /// fold change alone decides.
+    pub call: Option<Regulation>,
+}
+
+impl<'a> GeneComparison<'a> {
+    /// Compares the TPM values of `gene` in the tissues of two groups.
+    pub(crate) fn new(
+        gene: &'a DGEResult,
+        group_a: &[TPMValue],
+        group_b: &[TPMValue],
+        thresholds: &ComparisonThresholds,
+    ) -> Self {
+        let (mean_a, variance_a) = log_mean_variance(group_a);
+        let (mean_b, variance_b) = log_mean_variance(group_b);
+        let log2_fold_change = mean_a - mean_b;
+
+        let standard_error =
+            (variance_a / group_a.len() as f32 + variance_b / group_b.len() as f32).sqrt();
+        let z_score = (standard_error > 0.0).then(|| log2_fold_change / standard_error);
+
+        let significant = log2_fold_change.abs() >= thresholds.min_log2_fold_change
+            && z_score.is_none_or(|z| z.abs() >= thresholds.min_z_score);
+        Self {
+            gene,
+            log2_fold_change,
+            z_score,
+            call: significant.then_some(Regulation::of(log2_fold_change)),
+        }
+    }
+}
+
+// Mean and sample variance (0 for a single value) of log2(TPM + 1)
+fn log_mean_variance(tpms: &[TPMValue]) -> (f32, f32) {
+    let values: Vec<TPMValue> = tpms.iter().map(|x| (x.max(0.0) + 1.0).log2()).collect();
+    let (mean, sd) = mean_sd(&values);
+    let n = values.len() as f32;
+    let variance = if values.len() > 1 {
+        sd * sd * n / (n - 1.0)
+    } else {
+        0.0
+    };
+    (mean, variance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gene_comparison() {
+        let gene = DGEResult::new("Gene1".to_string(), "Symbol1".to_string());
+        let thresholds = ComparisonThresholds::default();
+
+        let up = GeneComparison::new(&gene, &[63.0, 127.0, 255.0], &[0.0, 1.0, 3.0], &thresholds);
+        // log2(TPM + 1) means: 7 against 1
+        assert!((up.log2_fold_change - 6.0).abs() < 1e-5);
+        assert!(up.z_score.unwrap() > 2.0);
+        assert_eq!(up.call, Some(Regulation::Up));
+
+        let noisy = GeneComparison::new(&gene, &[0.0, 1023.0], &[1.0, 3.0], &thresholds);
+        assert!(noisy.z_score.unwrap() < 2.0);
+        assert_eq!(noisy.call, None);
+
+        let single = GeneComparison::new(&gene, &[0.0], &[15.0], &thresholds);
+        assert_eq!(single.z_score, None);
+        assert_eq!(single.call, Some(Regulation::Down));
+    }
+}
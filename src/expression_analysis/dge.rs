@@ -1,7 +1,14 @@
+use super::scoring::median;
+use super::ExpressionStats;
 use super::GCTMetadata;
+use super::ScoringMethod;
+use super::SpecificityIndices;
 use super::TPMValue;
+use super::TissueIndex;
+use super::TissueTest;
 use super::ZScoreValue;
-use serde::{Serialize, Deserialize};
+use super::{ExpressionGate, ExpressionGates};
+use serde::{Deserialize, Serialize};
 
 /// Stores statistical information about the gene's differential expression across tissues.
 ///
@@ -10,17 +17,55 @@ use serde::{Serialize, Deserialize};
 pub struct DGEResult {
     pub id: String,                          // referred to as Name
     pub symbol: String,                      // referred to as Description
-    pub up_regulated: Vec<TissueAnalysis>,   // pair<TissueName, ZScoreValue>
-    pub down_regulated: Vec<TissueAnalysis>, // pair<TissueName, ZScoreValue>
+    pub up_regulated: Vec<TissueAnalysis>,   // pair<TissueIndex, ZScoreValue>
+    pub down_regulated: Vec<TissueAnalysis>, // pair<TissueIndex, ZScoreValue>
+    /// Row annotation values, aligned with `GCTMetadata::row_annotation_names` (GCT 1.3 only).
+    pub annotations: Vec<String>,
+    /// Whether the gene could be scored at all.
+    pub status: ExpressionStatus,
+    /// Up-regulated calls removed by the loader's expression gates.
+    pub suppressed: Vec<SuppressedCall>,
+    /// Tissue-specificity indices, if the loader was asked to compute them.
+    pub specificity: Option<SpecificityIndices>,
+    /// Sample-level tests, one per tissue and contrast, for loads with a `DifferentialTest`.
+    pub tissue_tests: Vec<TissueTest>,
+    /// Mean and SD of the analysed TPM values, reused by the gene-level selections.
+    pub expression: ExpressionStats,
 }
 
+/// Tells genes that were scored apart from genes that are not testable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ExpressionStatus {
+    /// The gene was scored; an empty up/down list means it is not differentially expressed.
+    Tested,
+    /// The TPM is 0 in every tissue.
+    NotExpressed,
+    /// The TPM is the same in every tissue, so z-scores are undefined.
+    Constant,
+}
+
+/// An up-regulated call that met the z-score threshold but failed an expression gate.
 #[derive(Debug, Serialize, Deserialize)]
+pub struct SuppressedCall {
+    pub tissue: TissueAnalysis,
+    pub gate: ExpressionGate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct TissueAnalysis {
-    pub tissue_name: String,
+    /// Column of the tissue in `GCTMetadata::column_names`, resolved with `tissue_name`.
+    pub tissue: TissueIndex,
     /// Z-scores for expression levels in the specific tissues with respect to all tissues.
     pub z_score: ZScoreValue,
 }
 
+impl TissueAnalysis {
+    /// Returns the name of the tissue in the metadata the result was computed with.
+    pub fn tissue_name<'a>(&self, metadata: &'a GCTMetadata) -> Option<&'a str> {
+        metadata.tissue_name(self.tissue)
+    }
+}
+
 impl DGEResult {
     pub fn new(id: String, symbol: String) -> Self {
         Self {
@@ -28,20 +73,36 @@ impl DGEResult {
             symbol,
             up_regulated: Vec::new(),
             down_regulated: Vec::new(),
+            annotations: Vec::new(),
+            status: ExpressionStatus::Tested,
+            suppressed: Vec::new(),
+            specificity: None,
+            tissue_tests: Vec::new(),
+            expression: ExpressionStats::default(),
         }
     }
-    pub fn add_up_regulated(&mut self, tissue_name: String, z_score: ZScoreValue) {
-        self.up_regulated.push(TissueAnalysis {
-            tissue_name,
-            z_score,
-        });
+
+    /// Returns `true` if the gene could be scored.
+    pub fn is_testable(&self) -> bool {
+        self.status == ExpressionStatus::Tested
+    }
+
+    /// Returns the value of the row annotation called `name`, if the file had one.
+    pub fn get_annotation(&self, metadata: &GCTMetadata, name: &str) -> Option<&str> {
+        metadata
+            .row_annotation_names
+            .iter()
+            .position(|annotation| annotation == name)
+            .and_then(|index| self.annotations.get(index))
+            .map(|value| value.as_str())
     }
 
-    pub fn add_down_regulated(&mut self, tissue_name: String, z_score: ZScoreValue) {
-        self.down_regulated.push(TissueAnalysis {
-            tissue_name,
-            z_score,
-        });
+    pub fn add_up_regulated(&mut self, tissue: TissueIndex, z_score: ZScoreValue) {
+        self.up_regulated.push(TissueAnalysis { tissue, z_score });
+    }
+
+    pub fn add_down_regulated(&mut self, tissue: TissueIndex, z_score: ZScoreValue) {
+        self.down_regulated.push(TissueAnalysis { tissue, z_score });
     }
 
     /// It compute differentially expressed genes based on Z-scores.
@@ -51,24 +112,88 @@ impl DGEResult {
         metadata: &GCTMetadata,
         dge_threshold: ZScoreValue,
     ) {
-        let tissue_names: &[String] = metadata.get_tissue_names();
+        self.perform_scored_analysis(tpms, metadata, dge_threshold, ScoringMethod::ZScore);
+    }
 
-        let mean: TPMValue = tpms.iter().copied().sum::<TPMValue>() / tpms.len() as TPMValue;
-        let variance: TPMValue =
-            tpms.iter().map(|x| (x - mean).powi(2)).sum::<TPMValue>() / tpms.len() as TPMValue;
+    /// It compute differentially expressed genes based on the scores of `scoring_method`.
+    pub fn perform_scored_analysis(
+        &mut self,
+        tpms: &[TPMValue],
+        metadata: &GCTMetadata,
+        dge_threshold: ZScoreValue,
+        scoring_method: ScoringMethod,
+    ) {
+        let first_tissue = metadata.first_tissue_column();
+        self.expression = ExpressionStats::from_tpms(tpms);
 
-        let sd: TPMValue = variance.sqrt();
+        // Genes without spread get an explicit status instead of NaN or infinite z-scores
+        if tpms.iter().all(|&tpm| tpm == 0.0) {
+            self.status = ExpressionStatus::NotExpressed;
+            return;
+        }
+        // Identical values are checked directly: the f32 SD of identical non-zero values is
+        // not exactly 0 and would give every tissue a z-score of +-1
+        if self.expression.min == self.expression.max {
+            self.status = ExpressionStatus::Constant;
+            return;
+        }
+        let (values, center, scale) = match scoring_method {
+            ScoringMethod::ZScore => (tpms.to_vec(), self.expression.mean, self.expression.sd),
+            _ => scoring_method.center_and_scale(tpms),
+        };
+        // Values that differ always have a positive scale, unless a TPM is not finite
+        if !(scale.is_finite() && scale > 0.0) {
+            self.status = ExpressionStatus::Constant;
+            return;
+        }
+        self.status = ExpressionStatus::Tested;
 
-        for (tissue, &tpm_value) in tissue_names.iter().zip(tpms.iter()) {
-            let zscore = (tpm_value - mean) / sd;
+        for (position, &value) in values.iter().enumerate().take(metadata.num_tissues) {
+            let tissue = (first_tissue + position) as TissueIndex;
+            let zscore = (value - center) / scale;
             if zscore >= dge_threshold {
-                self.add_up_regulated(tissue.clone(), zscore);
+                self.add_up_regulated(tissue, zscore);
             } else if zscore <= -dge_threshold {
-                self.add_down_regulated(tissue.clone(), zscore);
+                self.add_down_regulated(tissue, zscore);
             }
         }
     }
 
+    /// Moves the up-regulated calls failing one of the `gates` to `suppressed`.
+    pub fn apply_expression_gates(
+        &mut self,
+        tpms: &[TPMValue],
+        metadata: &GCTMetadata,
+        gates: &ExpressionGates,
+    ) {
+        if gates.is_disabled() || self.up_regulated.is_empty() {
+            return;
+        }
+        let first_tissue = metadata.first_tissue_column();
+        let median = median(tpms);
+        let expressed_tissues = gates.expressed_tissues(tpms);
+
+        let (kept, suppressed): (Vec<_>, Vec<_>) = std::mem::take(&mut self.up_regulated)
+            .into_iter()
+            .map(|tissue| {
+                let tpm = (tissue.tissue as usize)
+                    .checked_sub(first_tissue)
+                    .and_then(|position| tpms.get(position))
+                    .copied()
+                    .unwrap_or(0.0);
+                let gate = gates.check(tpm, median, expressed_tissues);
+                (tissue, gate)
+            })
+            .partition(|(_, gate)| gate.is_none());
+
+        self.up_regulated = kept.into_iter().map(|(tissue, _)| tissue).collect();
+        self.suppressed.extend(
+            suppressed
+                .into_iter()
+                .filter_map(|(tissue, gate)| gate.map(|gate| SuppressedCall { tissue, gate })),
+        );
+    }
+
     pub fn from_analysis(
         id: String,
         symbol: String,
@@ -80,4 +205,143 @@ impl DGEResult {
         dgeresult.perform_analysis(tpms, metadata, dge_threshold);
         dgeresult
     }
+
+    /// Returns a gene with the expression statistics of `tpms` and no calls, for the tests of
+    /// the gene-level selections.
+    #[cfg(test)]
+    pub(crate) fn with_expression(id: &str, tpms: &[TPMValue]) -> Self {
+        let mut gene = Self::new(id.to_string(), id.to_string());
+        gene.expression = ExpressionStats::from_tpms(tpms);
+        gene
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(num_tissues: usize) -> GCTMetadata {
+        let mut column_names = vec!["Name".to_string(), "Description".to_string()];
+        column_names.extend((1..=num_tissues).map(|i| format!("T{}", i)));
+        GCTMetadata::new(
+            "#1.2".to_string(),
+            1,
+            num_tissues + 2,
+            num_tissues,
+            column_names,
+        )
+    }
+
+    #[test]
+    fn test_not_expressed_gene() {
+        let dge = DGEResult::from_analysis(
+            "Gene1".to_string(),
+            "Symbol1".to_string(),
+            &[0.0, 0.0, 0.0],
+            &metadata(3),
+            2.0,
+        );
+        assert_eq!(dge.status, ExpressionStatus::NotExpressed);
+        assert!(!dge.is_testable());
+        assert!(dge.up_regulated.is_empty() && dge.down_regulated.is_empty());
+    }
+
+    #[test]
+    fn test_constant_gene() {
+        let dge = DGEResult::from_analysis(
+            "Gene1".to_string(),
+            "Symbol1".to_string(),
+            &[5.0, 5.0, 5.0],
+            &metadata(3),
+            2.0,
+        );
+        assert_eq!(dge.status, ExpressionStatus::Constant);
+    }
+
+    #[test]
+    fn test_identical_non_zero_values_are_constant() {
+        // The f32 SD of 54 values of 0.1 is about 5e-8 rather than 0
+        let tpms = [0.1; 54];
+        for scoring_method in [
+            ScoringMethod::ZScore,
+            ScoringMethod::RobustZScore,
+            ScoringMethod::Log2ZScore,
+        ] {
+            let mut dge = DGEResult::new("Gene1".to_string(), "Symbol1".to_string());
+            dge.perform_scored_analysis(&tpms, &metadata(tpms.len()), 0.5, scoring_method);
+            assert_eq!(dge.status, ExpressionStatus::Constant);
+            assert!(dge.up_regulated.is_empty() && dge.down_regulated.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_zero_mad_is_tested_for_robust_scores() {
+        // Real spread with a MAD of 0 is not a constant profile
+        let mut dge = DGEResult::new("Gene1".to_string(), "Symbol1".to_string());
+        dge.perform_scored_analysis(
+            &[0.0, 0.0, 0.0, 0.0, 0.2],
+            &metadata(5),
+            2.0,
+            ScoringMethod::RobustZScore,
+        );
+        assert_eq!(dge.status, ExpressionStatus::Tested);
+        assert!(dge.is_testable());
+        assert_eq!(dge.up_regulated.len(), 1);
+    }
+
+    #[test]
+    fn test_tissue_specific_gene_with_zero_mad_is_scored() {
+        // Most tissues at 0, as for testis- or pancreas-specific genes
+        let metadata = metadata(5);
+        let mut dge = DGEResult::new("Gene1".to_string(), "Symbol1".to_string());
+        dge.perform_scored_analysis(
+            &[0.0, 0.0, 0.0, 0.0, 50.0],
+            &metadata,
+            2.0,
+            ScoringMethod::RobustZScore,
+        );
+        assert!(dge.is_testable());
+        assert_eq!(dge.up_regulated.len(), 1);
+        assert_eq!(dge.up_regulated[0].tissue_name(&metadata), Some("T5"));
+        assert!(dge.down_regulated.is_empty());
+    }
+
+    #[test]
+    fn test_expression_gates_suppress_low_calls() {
+        // DDX11L1-like profile: z >= 2 in one tissue, but with a TPM of 0.17
+        let tpms = [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.02, 0.17];
+        let metadata = metadata(tpms.len());
+        let mut dge = DGEResult::from_analysis(
+            "Gene1".to_string(),
+            "Symbol1".to_string(),
+            &tpms,
+            &metadata,
+            2.0,
+        );
+        assert_eq!(dge.up_regulated.len(), 1);
+
+        let gates = ExpressionGates {
+            min_tpm: Some(1.0),
+            ..Default::default()
+        };
+        dge.apply_expression_gates(&tpms, &metadata, &gates);
+        assert!(dge.up_regulated.is_empty());
+        assert_eq!(dge.suppressed.len(), 1);
+        assert_eq!(dge.suppressed[0].tissue.tissue_name(&metadata), Some("T9"));
+        assert_eq!(dge.suppressed[0].gate, ExpressionGate::MinTpm);
+    }
+
+    #[test]
+    fn test_tested_gene() {
+        let dge = DGEResult::from_analysis(
+            "Gene1".to_string(),
+            "Symbol1".to_string(),
+            &[1.0, 1.0, 1.0, 1.0, 9.0],
+            &metadata(5),
+            1.5,
+        );
+        assert!(dge.is_testable());
+        assert_eq!(dge.up_regulated.len(), 1);
+        assert!(dge.up_regulated[0].z_score.is_finite());
+    }
 }
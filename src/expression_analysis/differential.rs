@@ -0,0 +1,529 @@
+use super::samples::SampleGrouping;
+use super::{DGEResult, GCTMetadata, GtexError, PValue, TPMValue, TissueIndex};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Statistical test run on the log2(TPM + 1) sample values of each gene.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum StatisticalTest {
+    /// Wilcoxon rank-sum / Mann–Whitney U test, normal approximation with tie and continuity
+    /// corrections.
+    #[default]
+    MannWhitneyU,
+    /// Welch's unequal-variance t-test.
+    WelchT,
+}
+
+/// Multiple-testing correction applied across genes, separately for each contrast.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PValueCorrection {
+    /// Benjamini–Hochberg false discovery rate.
+    #[default]
+    BenjaminiHochberg,
+    /// Bonferroni family-wise error rate.
+    Bonferroni,
+}
+
+/// Groups of samples compared for every gene.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Contrast {
+    /// Every tissue against the samples of all the other tissues.
+    OneVsRest,
+    /// The samples of `tissue` against the samples of `reference`.
+    Pair { tissue: String, reference: String },
+}
+
+/// Direction of a significant difference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Regulation {
+    Up,
+    Down,
+}
+
+impl Regulation {
+    /// Returns the direction of a difference; `Up` for positive values.
+    pub fn of(difference: f32) -> Self {
+        if difference > 0.0 {
+            Regulation::Up
+        } else {
+            Regulation::Down
+        }
+    }
+}
+
+/// Sample-level differential expression settings of `GtexSummaryLoader::load_samples`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DifferentialTest {
+    pub test: StatisticalTest,
+    pub correction: PValueCorrection,
+    pub contrasts: Vec<Contrast>,
+    /// Maximum adjusted p-value of a call.
+    pub max_q_value: PValue,
+    /// Minimum absolute log2 fold change of a call.
+    pub min_log2_fold_change: f32,
+}
+
+impl Default for DifferentialTest {
+    fn default() -> Self {
+        Self {
+            test: StatisticalTest::MannWhitneyU,
+            correction: PValueCorrection::BenjaminiHochberg,
+            contrasts: vec![Contrast::OneVsRest],
+            max_q_value: 0.05,
+            min_log2_fold_change: 1.0,
+        }
+    }
+}
+
+/// Result of a test of one gene in one tissue.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TissueTest {
+    /// Column of the tested tissue in `GCTMetadata::column_names`.
+    pub tissue: TissueIndex,
+    /// Column of the reference tissue, `None` for all the other tissues.
+    pub reference: Option<TissueIndex>,
+    /// Mean log2(TPM + 1) of the tissue minus the mean of the reference samples.
+    pub log2_fold_change: f32,
+    pub p_value: PValue,
+    /// Adjusted p-value across genes, set once the whole file has been loaded.
+    pub q_value: Option<PValue>,
+    /// Significant direction under the loader thresholds, set with `q_value`.
+    pub call: Option<Regulation>,
+}
+
+/// Contrasts resolved against the tissues of a sample-level load.
+#[derive(Debug)]
+pub(crate) struct SampleTests {
+    test: StatisticalTest,
+    /// Tissue positions compared, the reference being all the other tissues if `None`.
+    contrasts: Vec<(Option<usize>, Option<usize>)>,
+}
+
+impl SampleTests {
+    /// Resolves the contrast tissue names; fails with `GtexError::UnknownTissue`.
+    pub(crate) fn new(
+        config: &DifferentialTest,
+        metadata: &GCTMetadata,
+    ) -> Result<Self, GtexError> {
+        let position = |name: &str| {
+            metadata
+                .get_tissue_names()
+                .iter()
+                .position(|tissue| tissue == name)
+                .ok_or_else(|| GtexError::UnknownTissue(name.to_string()))
+        };
+        let contrasts = config
+            .contrasts
+            .iter()
+            .map(|contrast| match contrast {
+                Contrast::OneVsRest => Ok((None, None)),
+                Contrast::Pair { tissue, reference } => {
+                    Ok((Some(position(tissue)?), Some(position(reference)?)))
+                }
+            })
+            .collect::<Result<_, GtexError>>()?;
+        Ok(Self {
+            test: config.test,
+            contrasts,
+        })
+    }
+
+    /// Tests a row of sample values; groups with fewer than two samples are not tested.
+    pub(crate) fn run(&self, grouping: &SampleGrouping, tpms: &[TPMValue]) -> Vec<TissueTest> {
+        let first_tissue = grouping.metadata().first_tissue_column();
+        let index = |position: usize| (first_tissue + position) as TissueIndex;
+        let groups: Vec<Vec<f64>> = grouping
+            .columns()
+            .iter()
+            .map(|columns| {
+                columns
+                    .iter()
+                    .map(|&column| (tpms[column].max(0.0) as f64 + 1.0).log2())
+                    .collect()
+            })
+            .collect();
+
+        let mut tests = Vec::new();
+        for contrast in &self.contrasts {
+            match *contrast {
+                (Some(tissue), Some(reference)) => {
+                    let (a, b) = (&groups[tissue], &groups[reference]);
+                    if a.len() < 2 || b.len() < 2 {
+                        continue;
+                    }
+                    let p_value = match self.test {
+                        StatisticalTest::MannWhitneyU => {
+                            let pooled = [a.as_slice(), b.as_slice()].concat();
+                            let (ranks, tie_term) = mid_ranks(&pooled);
+                            let rank_sum = ranks[..a.len()].iter().sum();
+                            mann_whitney_p(rank_sum, a.len(), b.len(), tie_term)
+                        }
+                        StatisticalTest::WelchT => {
+                            welch_p(Moments::from_values(a), Moments::from_values(b))
+                        }
+                    };
+                    let fold_change = Moments::from_values(a).mean - Moments::from_values(b).mean;
+                    tests.push(TissueTest::new(
+                        index(tissue),
+                        Some(index(reference)),
+                        fold_change,
+                        p_value,
+                    ));
+                }
+                _ => tests.extend(self.one_vs_rest(&groups, index)),
+            }
+        }
+        tests
+    }
+
+    // Tests every tissue against the pooled samples of the others, ranking them only once
+    fn one_vs_rest(
+        &self,
+        groups: &[Vec<f64>],
+        index: impl Fn(usize) -> TissueIndex,
+    ) -> Vec<TissueTest> {
+        let pooled = groups.concat();
+        let total = Moments::from_values(&pooled);
+        let (ranks, tie_term) = match self.test {
+            StatisticalTest::MannWhitneyU => mid_ranks(&pooled),
+            StatisticalTest::WelchT => (Vec::new(), 0.0),
+        };
+
+        let mut tests = Vec::new();
+        let mut offset = 0;
+        for (position, group) in groups.iter().enumerate() {
+            let (start, end) = (offset, offset + group.len());
+            offset = end;
+            let rest_len = pooled.len() - group.len();
+            if group.len() < 2 || rest_len < 2 {
+                continue;
+            }
+            let moments = Moments::from_values(group);
+            let rest = total.without(&moments);
+            let p_value = match self.test {
+                StatisticalTest::MannWhitneyU => {
+                    let rank_sum = ranks[start..end].iter().sum();
+                    mann_whitney_p(rank_sum, group.len(), rest_len, tie_term)
+                }
+                StatisticalTest::WelchT => welch_p(moments, rest),
+            };
+            tests.push(TissueTest::new(
+                index(position),
+                None,
+                moments.mean - rest.mean,
+                p_value,
+            ));
+        }
+        tests
+    }
+}
+
+impl TissueTest {
+    /// Returns the name of the tested tissue.
+    pub fn tissue_name<'a>(&self, metadata: &'a GCTMetadata) -> Option<&'a str> {
+        metadata.tissue_name(self.tissue)
+    }
+
+    fn new(
+        tissue: TissueIndex,
+        reference: Option<TissueIndex>,
+        log2_fold_change: f64,
+        p_value: PValue,
+    ) -> Self {
+        Self {
+            tissue,
+            reference,
+            log2_fold_change: log2_fold_change as f32,
+            p_value,
+            q_value: None,
+            call: None,
+        }
+    }
+}
+
+/// Adjusts the p-values of every contrast across the genes of `results` and sets the calls.
+pub(crate) fn correct_tests(results: &mut HashMap<String, DGEResult>, config: &DifferentialTest) {
+    let mut families: HashMap<(TissueIndex, Option<TissueIndex>), Vec<&mut TissueTest>> =
+        HashMap::new();
+    for test in results
+        .values_mut()
+        .flat_map(|dge| dge.tissue_tests.iter_mut())
+    {
+        families
+            .entry((test.tissue, test.reference))
+            .or_default()
+            .push(test);
+    }
+
+    for tests in families.values_mut() {
+        let p_values: Vec<PValue> = tests.iter().map(|test| test.p_value).collect();
+        let q_values = adjust_p_values(&p_values, config.correction);
+        for (test, q_value) in tests.iter_mut().zip(q_values) {
+            test.q_value = Some(q_value);
+            let significant = q_value <= config.max_q_value
+                && test.log2_fold_change.abs() >= config.min_log2_fold_change;
+            test.call = significant.then_some(Regulation::of(test.log2_fold_change));
+        }
+    }
+}
+
+/// Adjusts a family of p-values for multiple testing, keeping their order.
+pub fn adjust_p_values(p_values: &[PValue], correction: PValueCorrection) -> Vec<PValue> {
+    let m = p_values.len() as PValue;
+    match correction {
+        PValueCorrection::Bonferroni => p_values.iter().map(|p| (p * m).min(1.0)).collect(),
+        PValueCorrection::BenjaminiHochberg => {
+            let mut order: Vec<usize> = (0..p_values.len()).collect();
+            order.sort_by(|&a, &b| p_values[a].total_cmp(&p_values[b]));
+            let mut q_values = vec![1.0; p_values.len()];
+            let mut running_min: PValue = 1.0;
+            for (rank, &i) in order.iter().enumerate().rev() {
+                running_min = running_min.min(p_values[i] * m / (rank + 1) as PValue);
+                q_values[i] = running_min;
+            }
+            q_values
+        }
+    }
+}
+
+/// Count, mean and sample variance of a group of values.
+#[derive(Debug, Clone, Copy)]
+struct Moments {
+    n: f64,
+    mean: f64,
+    sum_squares: f64,
+}
+
+impl Moments {
+    fn from_values(values: &[f64]) -> Self {
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let sum_squares = values.iter().map(|x| (x - mean).powi(2)).sum();
+        Self {
+            n,
+            mean,
+            sum_squares,
+        }
+    }
+
+    // Moments of the values of `self` that are not in `part`
+    fn without(&self, part: &Moments) -> Self {
+        let n = self.n - part.n;
+        let mean = (self.mean * self.n - part.mean * part.n) / n;
+        // Parallel variance formula, run backwards
+        let delta = part.mean - mean;
+        let sum_squares = self.sum_squares - part.sum_squares - delta * delta * part.n * n / self.n;
+        Self {
+            n,
+            mean,
+            sum_squares: sum_squares.max(0.0),
+        }
+    }
+
+    fn variance(&self) -> f64 {
+        self.sum_squares / (self.n - 1.0)
+    }
+}
+
+/// 1-based ranks with ties averaged, and the tie term sum(t^3 - t) over groups of ties.
+pub(crate) fn mid_ranks(values: &[f64]) -> (Vec<f64>, f64) {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+    let mut ranks = vec![0.0; values.len()];
+    let mut tie_term = 0.0;
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && values[order[end]] == values[order[start]] {
+            end += 1;
+        }
+        let rank = (start + end + 1) as f64 / 2.0;
+        for &i in &order[start..end] {
+            ranks[i] = rank;
+        }
+        let ties = (end - start) as f64;
+        tie_term += ties.powi(3) - ties;
+        start = end;
+    }
+    (ranks, tie_term)
+}
+
+/// Two-sided Mann–Whitney p-value from the rank sum of the first group.
+fn mann_whitney_p(rank_sum: f64, n1: usize, n2: usize, tie_term: f64) -> PValue {
+    let (n1, n2) = (n1 as f64, n2 as f64);
+    let n = n1 + n2;
+    let u = rank_sum - n1 * (n1 + 1.0) / 2.0;
+    let mean = n1 * n2 / 2.0;
+    let variance = n1 * n2 / 12.0 * ((n + 1.0) - tie_term / (n * (n - 1.0)));
+    if variance <= 0.0 {
+        return 1.0;
+    }
+    let z = ((u - mean).abs() - 0.5).max(0.0) / variance.sqrt();
+    erfc(z / std::f64::consts::SQRT_2).min(1.0)
+}
+
+/// Two-sided Welch t-test p-value.
+fn welch_p(a: Moments, b: Moments) -> PValue {
+    let (va, vb) = (a.variance() / a.n, b.variance() / b.n);
+    let se2 = va + vb;
+    if se2 <= 0.0 {
+        return if a.mean == b.mean { 1.0 } else { 0.0 };
+    }
+    let t = (a.mean - b.mean) / se2.sqrt();
+    let df = se2 * se2 / (va * va / (a.n - 1.0) + vb * vb / (b.n - 1.0));
+    incomplete_beta(df / 2.0, 0.5, df / (df + t * t)).min(1.0)
+}
+
+/// Complementary error function, with a relative error below 1.2e-7.
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807
+                            + t * (-1.13520398
+                                + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))));
+    let result = t * poly.exp();
+    if x >= 0.0 {
+        result
+    } else {
+        2.0 - result
+    }
+}
+
+/// Natural logarithm of the gamma function (Lanczos approximation).
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+    let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let mut series = 1.000000000190015;
+    for (i, coefficient) in COEFFICIENTS.iter().enumerate() {
+        series += coefficient / (x + 1.0 + i as f64);
+    }
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+/// Regularized incomplete beta function I_x(a, b).
+fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_continued_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_continued_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+// Continued fraction of the incomplete beta function (modified Lentz's method)
+fn beta_continued_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1.0 / d;
+    let mut h = d;
+    for m in 1..300 {
+        let m = m as f64;
+        let m2 = 2.0 * m;
+        for numerator in [
+            m * (b - m) * x / ((a + m2 - 1.0) * (a + m2)),
+            -(a + m) * (a + b + m) * x / ((a + m2) * (a + m2 + 1.0)),
+        ] {
+            d = 1.0 + numerator * d;
+            if d.abs() < TINY {
+                d = TINY;
+            }
+            c = 1.0 + numerator / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            d = 1.0 / d;
+            h *= d * c;
+        }
+        if (d * c - 1.0).abs() < 1e-12 {
+            break;
+        }
+    }
+    h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64, tolerance: f64) {
+        assert!((a - b).abs() < tolerance, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_mid_ranks() {
+        let (ranks, tie_term) = mid_ranks(&[3.0, 1.0, 3.0, 2.0]);
+        assert_eq!(ranks, [3.5, 1.0, 3.5, 2.0]);
+        assert_eq!(tie_term, 6.0);
+    }
+
+    #[test]
+    fn test_mann_whitney() {
+        // U = 0 against a mean of 12.5: z = 12 / sqrt(275 / 12) with the continuity correction
+        let values: Vec<f64> = (1..=10).map(f64::from).collect();
+        let (ranks, tie_term) = mid_ranks(&values);
+        let p = mann_whitney_p(ranks[..5].iter().sum(), 5, 5, tie_term);
+        assert_close(p, 0.012_185_8, 1e-6);
+    }
+
+    #[test]
+    fn test_welch() {
+        // t = -2.8947 with 5.52 degrees of freedom
+        let a = Moments::from_values(&[1.0, 2.0, 3.0, 4.0]);
+        let b = Moments::from_values(&[3.0, 5.0, 7.0, 9.0, 11.0]);
+        assert_close(welch_p(a, b), 0.030_286, 1e-5);
+    }
+
+    #[test]
+    fn test_student_t_quantile() {
+        // t = 2.228 is the two-sided 5% critical value with 10 degrees of freedom
+        assert_close(
+            incomplete_beta(5.0, 0.5, 10.0 / (10.0 + 2.228 * 2.228)),
+            0.05,
+            1e-4,
+        );
+    }
+
+    #[test]
+    fn test_moments_without() {
+        let all = Moments::from_values(&[1.0, 2.0, 4.0, 8.0, 16.0]);
+        let part = Moments::from_values(&[2.0, 8.0]);
+        let rest = all.without(&part);
+        let expected = Moments::from_values(&[1.0, 4.0, 16.0]);
+        assert_close(rest.mean, expected.mean, 1e-12);
+        assert_close(rest.variance(), expected.variance(), 1e-9);
+    }
+
+    #[test]
+    fn test_adjust_p_values() {
+        let p_values = [0.01, 0.04, 0.03, 0.5];
+        let bh = adjust_p_values(&p_values, PValueCorrection::BenjaminiHochberg);
+        for (q, expected) in bh.iter().zip([0.04, 0.0533333, 0.0533333, 0.5]) {
+            assert_close(*q, expected, 1e-6);
+        }
+        let bonferroni = adjust_p_values(&p_values, PValueCorrection::Bonferroni);
+        assert_eq!(bonferroni, [0.04, 0.16, 0.12, 1.0]);
+    }
+}
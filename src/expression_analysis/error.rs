@@ -0,0 +1,189 @@
+use std::fmt;
+use std::io;
+
+/// Errors raised while parsing a GCT file or analysing its rows.
+///
+/// Line numbers are 1-based positions in the file, including the metadata lines, and
+/// columns are 1-based positions within the row.
+#[derive(Debug)]
+pub enum GtexError {
+    /// The file ended before the metadata line `line` could be read.
+    MissingMetadataLine { line: usize },
+    /// The dimension line (second line) could not be parsed.
+    BadDimensionLine { content: String, reason: String },
+    /// The header (or a GCT 1.3 column annotation line) has the wrong number of columns.
+    HeaderLengthMismatch { expected: usize, found: usize },
+    /// A TPM value could not be parsed as a number.
+    InvalidTpm {
+        gene_id: String,
+        line: usize,
+        column: usize,
+        raw: String,
+    },
+    /// The same gene ID appears on two rows.
+    DuplicateGeneId {
+        id: String,
+        first_line: usize,
+        second_line: usize,
+    },
+    /// A row does not have as many values as the header declares.
+    RowLengthMismatch {
+        gene_id: String,
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// A tissue name is not one of the tissues of the metadata.
+    UnknownTissue(String),
+    /// No gene has this ID or symbol.
+    UnknownGene(String),
+    /// The sample attributes file has no column called this (e.g. `SAMPID` or `SMTSD`).
+    MissingAttributeColumn(String),
+    /// None of the sample columns of the GCT file has a tissue in the sample attributes.
+    NoTissueSamples,
+    /// A `DifferentialTest` was set on a load without sample-level data.
+    DifferentialTestWithoutSamples,
+    /// The tissue include and exclude lists leave no tissue to analyse.
+    NoTissuesSelected,
+    /// The query needs the TPM values, but the summary was loaded without the TPM matrix.
+    MissingTpmMatrix,
+    /// A tissue group of a comparison matches no tissue.
+    EmptyTissueGroup,
+    /// A tissue belongs to both groups of a comparison.
+    TissueInBothGroups(String),
+    /// A line of a tissue hierarchy file is not a `tissue<TAB>group` pair.
+    MalformedHierarchyLine { line: usize, content: String },
+    /// Reading the underlying file failed.
+    Io(io::Error),
+}
+
+impl GtexError {
+    /// Returns the 1-based line the error refers to, when it points at a specific row.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            GtexError::MissingMetadataLine { line }
+            | GtexError::InvalidTpm { line, .. }
+            | GtexError::RowLengthMismatch { line, .. }
+            | GtexError::MalformedHierarchyLine { line, .. } => Some(*line),
+            GtexError::DuplicateGeneId { second_line, .. } => Some(*second_line),
+            GtexError::BadDimensionLine { .. } => Some(2),
+            GtexError::HeaderLengthMismatch { .. }
+            | GtexError::UnknownTissue(_)
+            | GtexError::UnknownGene(_)
+            | GtexError::MissingAttributeColumn(_)
+            | GtexError::NoTissueSamples
+            | GtexError::DifferentialTestWithoutSamples
+            | GtexError::NoTissuesSelected
+            | GtexError::MissingTpmMatrix
+            | GtexError::EmptyTissueGroup
+            | GtexError::TissueInBothGroups(_)
+            | GtexError::Io(_) => None,
+        }
+    }
+
+    /// Returns the gene ID of the offending row, for row-level errors.
+    pub fn gene_id(&self) -> Option<&str> {
+        match self {
+            GtexError::InvalidTpm { gene_id, .. }
+            | GtexError::RowLengthMismatch { gene_id, .. } => Some(gene_id),
+            GtexError::DuplicateGeneId { id, .. } => Some(id),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for GtexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GtexError::MissingMetadataLine { .. } => write!(f, "Not enough metadata lines."),
+            GtexError::BadDimensionLine { reason, .. } => write!(f, "{}", reason),
+            GtexError::HeaderLengthMismatch { expected, found } => write!(
+                f,
+                "Invalid header length. Expected {} columns, but found {}.",
+                expected, found
+            ),
+            GtexError::InvalidTpm {
+                gene_id,
+                line,
+                column,
+                raw,
+            } => write!(
+                f,
+                "Invalid TPM value for gene ID {}: '{}', row number {}, column {}.",
+                gene_id, raw, line, column
+            ),
+            GtexError::DuplicateGeneId {
+                id,
+                first_line,
+                second_line,
+            } => write!(
+                f,
+                "Row with ID (Name) '{}' already exists on row number {}, duplicated on row number {}.",
+                id, first_line, second_line
+            ),
+            GtexError::RowLengthMismatch {
+                line,
+                expected,
+                found,
+                ..
+            } => write!(
+                f,
+                "Invalid number of tpm values with respect to the header, row number {}.\nExpected values: {}, found: {}.",
+                line, expected, found
+            ),
+            GtexError::UnknownTissue(tissue) => write!(f, "Unknown tissue '{}'.", tissue),
+            GtexError::UnknownGene(gene) => write!(f, "Unknown gene '{}'.", gene),
+            GtexError::MissingAttributeColumn(column) => {
+                write!(f, "Missing column '{}' in the sample attributes.", column)
+            }
+            GtexError::NoTissueSamples => {
+                write!(f, "No sample column has a tissue in the sample attributes.")
+            }
+            GtexError::DifferentialTestWithoutSamples => write!(
+                f,
+                "Differential tests need sample-level data; load the file with `load_samples`."
+            ),
+            GtexError::NoTissuesSelected => {
+                write!(f, "The tissue include and exclude lists leave no tissue.")
+            }
+            GtexError::MissingTpmMatrix => write!(
+                f,
+                "The TPM matrix was not kept; load the summary with `with_tpm_matrix(true)`."
+            ),
+            GtexError::EmptyTissueGroup => write!(f, "A tissue group is empty."),
+            GtexError::TissueInBothGroups(tissue) => {
+                write!(f, "Tissue '{}' is in both groups.", tissue)
+            }
+            GtexError::MalformedHierarchyLine { line, content } => write!(
+                f,
+                "Invalid tissue hierarchy line {}: '{}'. Expected a tissue and a group separated by a tab.",
+                line, content
+            ),
+            GtexError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for GtexError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GtexError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for GtexError {
+    fn from(e: io::Error) -> Self {
+        GtexError::Io(e)
+    }
+}
+
+impl From<GtexError> for io::Error {
+    fn from(e: GtexError) -> Self {
+        match e {
+            GtexError::Io(e) => e,
+            other => io::Error::new(io::ErrorKind::InvalidData, other),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use super::TPMValue;
+use serde::{Deserialize, Serialize};
+
+/// A gate that can suppress an up-regulated call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExpressionGate {
+    /// Too few tissues express the gene.
+    MinExpressedTissues,
+    /// The TPM in the flagged tissue is too low.
+    MinTpm,
+    /// The TPM in the flagged tissue is too close to the cross-tissue median.
+    MinFoldChange,
+}
+
+/// Expression-level requirements an up-regulated tissue must meet besides its z-score.
+///
+/// Every gate is disabled (`None`) by default. They are checked in the order
+/// `min_expressed_tissues`, `min_tpm`, `min_fold_change`, and the first failing one is
+/// recorded on the gene result.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct ExpressionGates {
+    /// Minimum TPM in the flagged tissue.
+    pub min_tpm: Option<TPMValue>,
+    /// Minimum ratio between the TPM in the flagged tissue and the median across tissues.
+    pub min_fold_change: Option<TPMValue>,
+    /// Minimum number of tissues where the gene is expressed (TPM >= `expressed_tpm`).
+    pub min_expressed_tissues: Option<usize>,
+    /// TPM from which a tissue counts as expressing the gene, 1.0 if `None`.
+    pub expressed_tpm: Option<TPMValue>,
+}
+
+impl ExpressionGates {
+    /// Returns `true` if no gate is set.
+    pub fn is_disabled(&self) -> bool {
+        self.min_tpm.is_none()
+            && self.min_fold_change.is_none()
+            && self.min_expressed_tissues.is_none()
+    }
+
+    /// Counts the tissues expressing the gene.
+    pub fn expressed_tissues(&self, tpms: &[TPMValue]) -> usize {
+        let expressed_tpm = self.expressed_tpm.unwrap_or(1.0);
+        tpms.iter().filter(|&&tpm| tpm >= expressed_tpm).count()
+    }
+
+    /// Returns the first gate failed by a tissue with TPM `tpm`, if any.
+    ///
+    /// `median` is the cross-tissue median TPM of the gene and `expressed_tissues` the
+    /// result of `expressed_tissues`.
+    pub fn check(
+        &self,
+        tpm: TPMValue,
+        median: TPMValue,
+        expressed_tissues: usize,
+    ) -> Option<ExpressionGate> {
+        if let Some(min_expressed_tissues) = self.min_expressed_tissues {
+            if expressed_tissues < min_expressed_tissues {
+                return Some(ExpressionGate::MinExpressedTissues);
+            }
+        }
+        if let Some(min_tpm) = self.min_tpm {
+            if tpm < min_tpm {
+                return Some(ExpressionGate::MinTpm);
+            }
+        }
+        if let Some(min_fold_change) = self.min_fold_change {
+            // A median of 0 gives an infinite fold change for any expressed tissue
+            if tpm / median < min_fold_change || tpm == 0.0 {
+                return Some(ExpressionGate::MinFoldChange);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_gates_pass() {
+        let gates = ExpressionGates::default();
+        assert!(gates.is_disabled());
+        assert_eq!(gates.check(0.02, 0.0, 0), None);
+    }
+
+    #[test]
+    fn test_gate_order() {
+        let gates = ExpressionGates {
+            min_tpm: Some(1.0),
+            min_fold_change: Some(4.0),
+            min_expressed_tissues: Some(1),
+            expressed_tpm: None,
+        };
+        assert_eq!(
+            gates.check(0.5, 0.1, 0),
+            Some(ExpressionGate::MinExpressedTissues)
+        );
+        assert_eq!(gates.check(0.5, 0.1, 1), Some(ExpressionGate::MinTpm));
+        assert_eq!(
+            gates.check(3.0, 1.0, 1),
+            Some(ExpressionGate::MinFoldChange)
+        );
+        assert_eq!(gates.check(5.0, 1.0, 1), None);
+        assert_eq!(gates.check(5.0, 0.0, 1), None);
+    }
+
+    #[test]
+    fn test_expressed_tissues() {
+        let mut gates = ExpressionGates::default();
+        assert_eq!(gates.expressed_tissues(&[0.0, 0.5, 1.0, 10.0]), 2);
+        gates.expressed_tpm = Some(0.1);
+        assert_eq!(gates.expressed_tissues(&[0.0, 0.5, 1.0, 10.0]), 3);
+    }
+}
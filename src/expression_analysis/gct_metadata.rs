@@ -1,10 +1,18 @@
+use super::{GtexError, TissueIndex};
+use serde::{Deserialize, Serialize};
 use std::io;
-use serde::{Serialize, Deserialize};
+
+/// A named column annotation of a GCT 1.3 file, with one value per tissue/sample column.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnAnnotation {
+    pub name: String,
+    pub values: Vec<String>,
+}
 
 /// Stores metadata information about a GTEx dataset.
 ///
 /// Includes dataset version, sample counts, and column headers.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GCTMetadata {
     /// Version of the GCT format used in the file.
     pub version: String,
@@ -19,6 +27,12 @@ pub struct GCTMetadata {
 
     /// Names of all columns, including sample IDs.
     pub column_names: Vec<String>,
+
+    /// Names of the row descriptor columns following the ID (GCT 1.3 only).
+    pub row_annotation_names: Vec<String>,
+
+    /// Column annotation rows, one value per tissue/sample (GCT 1.3 only).
+    pub column_annotations: Vec<ColumnAnnotation>,
 }
 
 impl GCTMetadata {
@@ -35,42 +49,110 @@ impl GCTMetadata {
             num_columns,
             num_tissues,
             column_names,
+            row_annotation_names: Vec::new(),
+            column_annotations: Vec::new(),
         }
     }
 
     /// Returns the names of all the tissues, which are all the columns excluding ID/Symbol columns.
     pub fn get_tissue_names(&self) -> &[String] {
-        &self.column_names[2..]
+        &self.column_names[self.num_columns - self.num_tissues..]
+    }
+
+    /// Returns the name of the tissue at column `index`, or `None` if it is not a tissue column.
+    pub fn tissue_name(&self, index: TissueIndex) -> Option<&str> {
+        let index = index as usize;
+        if index < self.first_tissue_column() {
+            return None;
+        }
+        self.column_names.get(index).map(|name| name.as_str())
+    }
+
+    /// Returns the column of the tissue called `name`.
+    pub fn tissue_index(&self, name: &str) -> Option<TissueIndex> {
+        self.get_tissue_names()
+            .iter()
+            .position(|tissue| tissue == name)
+            .map(|position| (self.first_tissue_column() + position) as TissueIndex)
+    }
+
+    /// Returns the columns of the tissues matching any of `patterns`, in metadata order.
+    ///
+    /// Patterns are tissue names or globs where `*` matches any run of characters and `?` a
+    /// single one, e.g. "Brain_*". Fails with `GtexError::UnknownTissue` for a pattern that
+    /// matches no tissue.
+    pub fn match_tissues(&self, patterns: &[&str]) -> Result<Vec<TissueIndex>, GtexError> {
+        if let Some(pattern) = patterns.iter().find(|pattern| {
+            !self
+                .get_tissue_names()
+                .iter()
+                .any(|tissue| glob_match(pattern, tissue))
+        }) {
+            return Err(GtexError::UnknownTissue(pattern.to_string()));
+        }
+        Ok(self
+            .get_tissue_names()
+            .iter()
+            .enumerate()
+            .filter(|(_, tissue)| patterns.iter().any(|pattern| glob_match(pattern, tissue)))
+            .map(|(position, _)| (self.first_tissue_column() + position) as TissueIndex)
+            .collect())
+    }
+
+    /// Returns a copy of the metadata with `tissues` in place of the tissue columns.
+    ///
+    /// The ID and row annotation columns are kept, while the per-column annotations are dropped.
+    pub fn with_tissue_columns(&self, tissues: Vec<String>) -> GCTMetadata {
+        let mut column_names = self.column_names[..self.first_tissue_column()].to_vec();
+        let num_tissues = tissues.len();
+        column_names.extend(tissues);
+        let mut metadata = GCTMetadata::new(
+            self.version.to_string(),
+            self.num_rows,
+            column_names.len(),
+            num_tissues,
+            column_names,
+        );
+        metadata.row_annotation_names = self.row_annotation_names.clone();
+        metadata
+    }
+
+    /// Returns the column of the first tissue, following the ID and annotation columns.
+    pub fn first_tissue_column(&self) -> usize {
+        self.num_columns - self.num_tissues
+    }
+
+    /// Returns `true` if the metadata was read from a GCT 1.3 file.
+    pub fn is_v1_3(&self) -> bool {
+        self.version.trim() == "#1.3"
     }
 
-    /// Generate a io::Result<GCTMetadata> from the file lines iterator and returns it.
+    /// Returns the values of the column annotation called `name`, one per tissue.
+    pub fn get_column_annotation(&self, name: &str) -> Option<&[String]> {
+        self.column_annotations
+            .iter()
+            .find(|annotation| annotation.name == name)
+            .map(|annotation| annotation.values.as_slice())
+    }
+
+    /// Generate a Result<GCTMetadata, GtexError> from the file lines iterator and returns it.
     pub fn from_lines(
         mut lines: impl Iterator<Item = io::Result<String>>,
-    ) -> io::Result<GCTMetadata> {
+    ) -> Result<GCTMetadata, GtexError> {
         // Read the first three lines
-        let version = lines.next().ok_or_else(|| {
-            io::Error::new(io::ErrorKind::InvalidData, "Not enough metadata lines.")
-        })??;
-        let size_line = lines.next().ok_or_else(|| {
-            io::Error::new(io::ErrorKind::InvalidData, "Not enough metadata lines.")
-        })??;
-        let header_line = lines.next().ok_or_else(|| {
-            io::Error::new(io::ErrorKind::InvalidData, "Not enough metadata lines.")
-        })??;
+        let version = next_metadata_line(&mut lines, 1)?;
+        let size_line = next_metadata_line(&mut lines, 2)?;
+        let header_line = next_metadata_line(&mut lines, 3)?;
 
         let sizes: Vec<&str> = size_line.split_whitespace().collect();
         if sizes.len() < 2 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
+            return Err(bad_dimension_line(
+                &size_line,
                 "Invalid size line format. Expected at least two values.",
             ));
         }
-        let num_rows = sizes[0]
-            .parse::<usize>()
-            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid row count format"))?;
-        let num_tissues = sizes[1].parse::<usize>().map_err(|_| {
-            io::Error::new(io::ErrorKind::InvalidData, "Invalid tissue count format")
-        })?;
+        let num_rows = parse_size(&size_line, sizes[0], "row")?;
+        let num_tissues = parse_size(&size_line, sizes[1], "tissue")?;
 
         let num_columns = num_tissues + 2;
         let column_names: Vec<String> = header_line
@@ -79,24 +161,143 @@ impl GCTMetadata {
             .collect();
 
         if column_names.len() != num_columns {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!(
-                    "Invalid header length. Expected {} columns, but found {}.",
-                    num_columns,
-                    column_names.len()
-                ),
-            ));
+            return Err(GtexError::HeaderLengthMismatch {
+                expected: num_columns,
+                found: column_names.len(),
+            });
         }
 
-        Ok(Self {
+        Ok(Self::new(
             version,
             num_rows,
             num_columns,
             num_tissues,
             column_names,
-        })
+        ))
     }
+
+    /// Generate a Result<GCTMetadata, GtexError> from the lines of a GCT 1.3 file and returns it.
+    ///
+    /// The dimension line holds four values (rows, data columns, row annotations, column
+    /// annotations), the header lists the ID, the row annotation names and the tissues, and
+    /// it is followed by one line per column annotation. The header and annotation lines are
+    /// split on tabs, so names and values may contain spaces ("Brain - Cortex").
+    pub fn from_lines_v1_3(
+        mut lines: impl Iterator<Item = io::Result<String>>,
+    ) -> Result<GCTMetadata, GtexError> {
+        let version = next_metadata_line(&mut lines, 1)?;
+        let size_line = next_metadata_line(&mut lines, 2)?;
+        let header_line = next_metadata_line(&mut lines, 3)?;
+
+        let sizes: Vec<&str> = size_line.split_whitespace().collect();
+        if sizes.len() != 4 {
+            return Err(bad_dimension_line(
+                &size_line,
+                "Invalid size line format. Expected four values.",
+            ));
+        }
+        let num_rows = parse_size(&size_line, sizes[0], "row")?;
+        let num_tissues = parse_size(&size_line, sizes[1], "tissue")?;
+        let num_row_annotations = parse_size(&size_line, sizes[2], "row annotation")?;
+        let num_column_annotations = parse_size(&size_line, sizes[3], "column annotation")?;
+
+        let num_columns = 1 + num_row_annotations + num_tissues;
+        let column_names: Vec<String> = split_tab_fields(&header_line)
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        if column_names.len() != num_columns {
+            return Err(GtexError::HeaderLengthMismatch {
+                expected: num_columns,
+                found: column_names.len(),
+            });
+        }
+
+        let mut column_annotations = Vec::with_capacity(num_column_annotations);
+        for index in 0..num_column_annotations {
+            let line = next_metadata_line(&mut lines, 4 + index)?;
+            let fields = split_tab_fields(&line);
+            if fields.len() != num_columns {
+                return Err(GtexError::HeaderLengthMismatch {
+                    expected: num_columns,
+                    found: fields.len(),
+                });
+            }
+            column_annotations.push(ColumnAnnotation {
+                name: fields[0].to_string(),
+                values: fields[1 + num_row_annotations..]
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect(),
+            });
+        }
+
+        let mut metadata = Self::new(version, num_rows, num_columns, num_tissues, column_names);
+        metadata.row_annotation_names = metadata.column_names[1..1 + num_row_annotations].to_vec();
+        metadata.column_annotations = column_annotations;
+        Ok(metadata)
+    }
+}
+
+/// Splits a tab-delimited GCT 1.3 line into its fields, without surrounding whitespace.
+pub(crate) fn split_tab_fields(line: &str) -> Vec<&str> {
+    line.split('\t').map(str::trim).collect()
+}
+
+/// Returns `true` if `name` matches the glob `pattern` (`*` for any run of characters, `?`
+/// for one character).
+pub(crate) fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` and of the name character it is matched up to
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// Reads the metadata line at the 1-based position `line`
+fn next_metadata_line(
+    lines: &mut impl Iterator<Item = io::Result<String>>,
+    line: usize,
+) -> Result<String, GtexError> {
+    Ok(lines
+        .next()
+        .ok_or(GtexError::MissingMetadataLine { line })??)
+}
+
+fn bad_dimension_line(content: &str, reason: &str) -> GtexError {
+    GtexError::BadDimensionLine {
+        content: content.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+// Parses one of the counts of the dimension line
+fn parse_size(content: &str, value: &str, what: &str) -> Result<usize, GtexError> {
+    value
+        .parse::<usize>()
+        .map_err(|_| bad_dimension_line(content, &format!("Invalid {} count format", what)))
 }
 
 #[cfg(test)]
@@ -202,4 +403,142 @@ mod tests {
             .to_string()
             .contains("Invalid tissue count"));
     }
+
+    #[test]
+    fn test_gct_metadata_from_lines_v1_3() {
+        let input = vec![
+            Ok("#1.3".to_string()),
+            Ok("2 3 2 1".to_string()),
+            Ok("id\tSymbol\tBiotype\tT1\tT2\tT3".to_string()),
+            Ok("Group\tna\tna\tBrain\tBrain\tLiver".to_string()),
+        ];
+        let metadata: GCTMetadata = GCTMetadata::from_lines_v1_3(input.into_iter()).unwrap();
+        assert!(metadata.is_v1_3());
+        assert_eq!(metadata.num_rows, 2);
+        assert_eq!(metadata.num_tissues, 3);
+        assert_eq!(metadata.num_columns, 6);
+        assert_eq!(metadata.get_tissue_names(), ["T1", "T2", "T3"]);
+        assert_eq!(metadata.row_annotation_names, ["Symbol", "Biotype"]);
+        assert_eq!(metadata.tissue_index("T2"), Some(4));
+        assert_eq!(metadata.tissue_name(4), Some("T2"));
+        assert_eq!(metadata.tissue_name(2), None);
+        assert_eq!(metadata.tissue_index("Biotype"), None);
+        assert_eq!(
+            metadata.get_column_annotation("Group").unwrap(),
+            ["Brain", "Brain", "Liver"]
+        );
+    }
+
+    #[test]
+    fn test_v1_3_fields_with_spaces() {
+        let input = vec![
+            Ok("#1.3".to_string()),
+            Ok("1\t2\t1\t1".to_string()),
+            Ok("id\tSymbol\tBrain - Cortex\tWhole Blood".to_string()),
+            Ok("SMTSD\tna\tBrain - Cortex\tWhole Blood".to_string()),
+        ];
+        let metadata = GCTMetadata::from_lines_v1_3(input.into_iter()).unwrap();
+        assert_eq!(
+            metadata.get_tissue_names(),
+            ["Brain - Cortex", "Whole Blood"]
+        );
+        assert_eq!(
+            metadata.get_column_annotation("SMTSD").unwrap(),
+            ["Brain - Cortex", "Whole Blood"]
+        );
+    }
+
+    #[test]
+    fn test_invalid_size_format_v1_3() {
+        let input = vec![
+            Ok("#1.3".to_string()),
+            Ok("2 3".to_string()),
+            Ok("id\tT1\tT2\tT3".to_string()),
+        ];
+        let result = GCTMetadata::from_lines_v1_3(input.into_iter());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid size line format. Expected four values."
+        );
+    }
+
+    #[test]
+    fn test_missing_column_annotation_v1_3() {
+        let input = vec![
+            Ok("#1.3".to_string()),
+            Ok("2 3 0 1".to_string()),
+            Ok("id\tT1\tT2\tT3".to_string()),
+        ];
+        let result = GCTMetadata::from_lines_v1_3(input.into_iter());
+        assert!(matches!(
+            result.unwrap_err(),
+            GtexError::MissingMetadataLine { line: 4 }
+        ));
+    }
+
+    #[test]
+    fn test_header_length_mismatch_variant() {
+        let input = vec![
+            Ok("v1.2".to_string()),
+            Ok("100 2".to_string()),
+            Ok("ID SYMBOL Sample1 ".to_string()),
+        ];
+        let result = GCTMetadata::from_lines(input.into_iter());
+        assert!(matches!(
+            result.unwrap_err(),
+            GtexError::HeaderLengthMismatch {
+                expected: 4,
+                found: 3
+            }
+        ));
+    }
+
+    #[test]
+    fn test_bad_dimension_line_variant() {
+        let input = vec![
+            Ok("v1.2".to_string()),
+            Ok("1a0 2".to_string()),
+            Ok("ID SYMBOL Sample1 Sample2".to_string()),
+        ];
+        let error = GCTMetadata::from_lines(input.into_iter()).unwrap_err();
+        assert!(matches!(error, GtexError::BadDimensionLine { .. }));
+        assert_eq!(error.line(), Some(2));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("Brain_*", "Brain_Cortex"));
+        assert!(glob_match("*Cortex*", "Brain_Frontal_Cortex_BA9"));
+        assert!(glob_match("Colon_?igmoid", "Colon_Sigmoid"));
+        assert!(glob_match("Liver", "Liver"));
+        assert!(!glob_match("Brain_*", "Liver"));
+        assert!(!glob_match("Liver", "Liver_Extra"));
+    }
+
+    #[test]
+    fn test_match_tissues() {
+        let column_names = [
+            "Name",
+            "Description",
+            "Brain_Cortex",
+            "Liver",
+            "Brain_Cerebellum",
+        ];
+        let metadata = GCTMetadata::new(
+            "#1.2".to_string(),
+            1,
+            5,
+            3,
+            column_names.iter().map(|name| name.to_string()).collect(),
+        );
+        assert_eq!(metadata.match_tissues(&["Brain_*"]).unwrap(), [2, 4]);
+        assert_eq!(
+            metadata.match_tissues(&["Liver", "Brain_C*"]).unwrap(),
+            [2, 3, 4]
+        );
+        assert!(matches!(
+            metadata.match_tissues(&["Testis"]),
+            Err(GtexError::UnknownTissue(tissue)) if tissue == "Testis"
+        ));
+    }
 }
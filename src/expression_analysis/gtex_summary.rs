@@ -1,11 +1,33 @@
 // use crate::models::{Metadata, Results};
+use super::coexpression::Profile;
+use super::differential::{correct_tests, SampleTests};
+use super::gct_metadata::split_tab_fields;
+use super::hierarchy::CollapsedColumns;
+use super::housekeeping::find_housekeeping_genes;
+use super::pca::log_column;
+use super::samples::{SampleAggregatesBuilder, SampleGrouping, TissueStats};
+use super::selection::{SelectedColumns, TissueFilter};
+use super::tpm_matrix::TpmMatrixBuilder;
+use super::variable_genes::select_variable_genes;
 use super::TPMValue;
-use super::{DGEResult, GCTMetadata, ZScoreValue};
-use std::collections::HashMap;
-use std::io::{self, BufRead, Error, ErrorKind};
-use serde::{Serialize, Deserialize};
+use super::{
+    CoexpressedGene, CoexpressionEdge, CoexpressionOptions, CollapseMethod, ComparisonThresholds,
+    CorrelationMethod, DGEResult, DifferentialTest, ErrorPolicy, ExpressionGates, ExpressionStatus,
+    GCTMetadata, GeneComparison, GtexError, HousekeepingCriteria, HousekeepingGene, Pca,
+    RowCountStatus, SampleAggregates, SampleAttributes, ScoringMethod, SpecificityIndices,
+    SpecificityMetric, TissueHierarchy, TissueIndex, TissueMatrix, TpmMatrix, ValidationReport,
+    VariabilityMethod, VariableGene, ZScoreValue,
+};
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
 use std::fs::File;
+use std::io::{self, BufRead, Lines, Read, Write};
 use std::io::{BufReader, BufWriter};
+use std::iter::Peekable;
 use std::path::Path;
 
 /// Represents a summary of GTEx gene expression data analysis, including metadata and processed results.
@@ -16,11 +38,510 @@ use std::path::Path;
 pub struct GtexSummary {
     pub metadata: GCTMetadata,
     results: HashMap<String, DGEResult>,
+    validation: ValidationReport,
+    row_count: RowCountStatus,
+    scoring_method: ScoringMethod,
+    /// TPM values of the loaded genes, kept with `GtexSummaryLoader::with_tpm_matrix`.
+    tpm_matrix: Option<TpmMatrix>,
+    /// Per-tissue statistics, for summaries loaded with `GtexSummaryLoader::load_samples`.
+    sample_aggregates: Option<SampleAggregates>,
+    /// Housekeeping candidates, the most stable first, set by `classify_housekeeping`.
+    housekeeping: Vec<HousekeepingGene>,
+    /// Gene IDs by symbol, rebuilt rather than cached.
+    #[serde(skip)]
+    symbol_index: HashMap<String, Vec<String>>,
+    /// Gene IDs by unversioned Ensembl ID, rebuilt rather than cached.
+    #[serde(skip)]
+    ensembl_base_index: HashMap<String, Vec<String>>,
+    /// Up/down-regulated genes by tissue, rebuilt rather than cached.
+    #[serde(skip)]
+    tissue_index: HashMap<TissueIndex, TissueCalls>,
+}
+
+/// Genes called up or down-regulated in one tissue, the most extreme z-score first.
+#[derive(Debug, Default)]
+struct TissueCalls {
+    up: Vec<(String, ZScoreValue)>,
+    down: Vec<(String, ZScoreValue)>,
 }
 
 impl GtexSummary {
     pub fn new(metadata: GCTMetadata, results: HashMap<String, DGEResult>) -> Self {
-        Self { metadata, results }
+        let row_count = RowCountStatus::compare(metadata.num_rows, results.len());
+        let mut summary = Self {
+            metadata,
+            results,
+            validation: ValidationReport::default(),
+            row_count,
+            scoring_method: ScoringMethod::ZScore,
+            tpm_matrix: None,
+            sample_aggregates: None,
+            housekeeping: Vec::new(),
+            symbol_index: HashMap::new(),
+            ensembl_base_index: HashMap::new(),
+            tissue_index: HashMap::new(),
+        };
+        summary.build_indexes();
+        summary
+    }
+
+    // Builds the secondary gene indexes, with the IDs of each entry sorted
+    fn build_indexes(&mut self) {
+        if let Some(tpm_matrix) = &mut self.tpm_matrix {
+            tpm_matrix.build_index();
+        }
+        if let Some(sample_aggregates) = &mut self.sample_aggregates {
+            sample_aggregates.build_index();
+        }
+        self.symbol_index.clear();
+        self.ensembl_base_index.clear();
+        for (id, dge) in &self.results {
+            self.symbol_index
+                .entry(dge.symbol.to_string())
+                .or_default()
+                .push(id.to_string());
+            self.ensembl_base_index
+                .entry(ensembl_base(id).to_string())
+                .or_default()
+                .push(id.to_string());
+        }
+        for ids in self
+            .symbol_index
+            .values_mut()
+            .chain(self.ensembl_base_index.values_mut())
+        {
+            ids.sort();
+        }
+
+        self.tissue_index.clear();
+        for (id, dge) in &self.results {
+            for tissue in &dge.up_regulated {
+                self.tissue_index
+                    .entry(tissue.tissue)
+                    .or_default()
+                    .up
+                    .push((id.to_string(), tissue.z_score));
+            }
+            for tissue in &dge.down_regulated {
+                self.tissue_index
+                    .entry(tissue.tissue)
+                    .or_default()
+                    .down
+                    .push((id.to_string(), tissue.z_score));
+            }
+        }
+        for calls in self.tissue_index.values_mut() {
+            calls
+                .up
+                .sort_by(|(a_id, a), (b_id, b)| b.total_cmp(a).then_with(|| a_id.cmp(b_id)));
+            calls
+                .down
+                .sort_by(|(a_id, a), (b_id, b)| a.total_cmp(b).then_with(|| a_id.cmp(b_id)));
+        }
+    }
+
+    /// Returns the genes up-regulated in `tissue`, highest z-score first, keeping the first
+    /// `top_n` if given.
+    ///
+    /// Fails with `GtexError::UnknownTissue` if `tissue` is not a tissue of the metadata.
+    pub fn genes_up_in(
+        &self,
+        tissue: &str,
+        top_n: Option<usize>,
+    ) -> Result<Vec<(&DGEResult, ZScoreValue)>, GtexError> {
+        self.tissue_calls(tissue, top_n, |calls| &calls.up)
+    }
+
+    /// Returns the genes down-regulated in `tissue`, lowest z-score first, keeping the first
+    /// `top_n` if given.
+    ///
+    /// Fails with `GtexError::UnknownTissue` if `tissue` is not a tissue of the metadata.
+    pub fn genes_down_in(
+        &self,
+        tissue: &str,
+        top_n: Option<usize>,
+    ) -> Result<Vec<(&DGEResult, ZScoreValue)>, GtexError> {
+        self.tissue_calls(tissue, top_n, |calls| &calls.down)
+    }
+
+    fn tissue_calls(
+        &self,
+        tissue: &str,
+        top_n: Option<usize>,
+        select: impl Fn(&TissueCalls) -> &Vec<(String, ZScoreValue)>,
+    ) -> Result<Vec<(&DGEResult, ZScoreValue)>, GtexError> {
+        let Some(index) = self.metadata.tissue_index(tissue) else {
+            return Err(GtexError::UnknownTissue(tissue.to_string()));
+        };
+        let Some(calls) = self.tissue_index.get(&index) else {
+            return Ok(Vec::new());
+        };
+        Ok(select(calls)
+            .iter()
+            .take(top_n.unwrap_or(usize::MAX))
+            .map(|(id, z_score)| (&self.results[id], *z_score))
+            .collect())
+    }
+
+    /// Compares every gene between two groups of tissues, with the default
+    /// `ComparisonThresholds`; see `compare_with`.
+    pub fn compare(
+        &self,
+        group_a: &[&str],
+        group_b: &[&str],
+    ) -> Result<Vec<GeneComparison<'_>>, GtexError> {
+        self.compare_with(group_a, group_b, &ComparisonThresholds::default())
+    }
+
+    /// Compares every gene between two groups of tissues, in the order of the TPM matrix.
+    ///
+    /// Groups are lists of tissue names or glob patterns (e.g. `["Brain_*"]`), validated
+    /// against the metadata tissues; an empty `group_b` stands for every tissue outside
+    /// `group_a`. Needs the TPM matrix (`GtexSummaryLoader::with_tpm_matrix`).
+    pub fn compare_with(
+        &self,
+        group_a: &[&str],
+        group_b: &[&str],
+        thresholds: &ComparisonThresholds,
+    ) -> Result<Vec<GeneComparison<'_>>, GtexError> {
+        let tpm_matrix = self
+            .tpm_matrix
+            .as_ref()
+            .ok_or(GtexError::MissingTpmMatrix)?;
+        let tissues_a = self.metadata.match_tissues(group_a)?;
+        let tissues_b = if group_b.is_empty() {
+            let all_tissues = self.metadata.match_tissues(&["*"])?;
+            all_tissues
+                .into_iter()
+                .filter(|tissue| !tissues_a.contains(tissue))
+                .collect()
+        } else {
+            self.metadata.match_tissues(group_b)?
+        };
+        if tissues_a.is_empty() || tissues_b.is_empty() {
+            return Err(GtexError::EmptyTissueGroup);
+        }
+        if let Some(&tissue) = tissues_a.iter().find(|tissue| tissues_b.contains(tissue)) {
+            let name = self.metadata.tissue_name(tissue).unwrap_or_default();
+            return Err(GtexError::TissueInBothGroups(name.to_string()));
+        }
+
+        let first_tissue = self.metadata.first_tissue_column();
+        let columns = |tissues: &[TissueIndex]| -> Vec<&[TPMValue]> {
+            tissues
+                .iter()
+                .map(|&tissue| tpm_matrix.column(tissue as usize - first_tissue))
+                .collect()
+        };
+        let (columns_a, columns_b) = (columns(&tissues_a), columns(&tissues_b));
+
+        Ok(tpm_matrix
+            .gene_ids()
+            .iter()
+            .enumerate()
+            .map(|(gene, id)| {
+                let values_a: Vec<TPMValue> = columns_a.iter().map(|column| column[gene]).collect();
+                let values_b: Vec<TPMValue> = columns_b.iter().map(|column| column[gene]).collect();
+                GeneComparison::new(&self.results[id], &values_a, &values_b, thresholds)
+            })
+            .collect())
+    }
+
+    /// Returns the `options.top_k` genes whose tissue profile correlates best with that of
+    /// `gene`, highest correlation first.
+    ///
+    /// `gene` is a gene ID or, failing that, a symbol (e.g. "ALB"); the first gene by ID is
+    /// used for symbols shared by several genes. Profiles follow the tissue order of the
+    /// metadata, and genes with the same value in every tissue are left out. Needs the TPM
+    /// matrix (`GtexSummaryLoader::with_tpm_matrix`).
+    pub fn coexpressed_genes(
+        &self,
+        gene: &str,
+        options: &CoexpressionOptions,
+    ) -> Result<Vec<CoexpressedGene<'_>>, GtexError> {
+        let tpm_matrix = self
+            .tpm_matrix
+            .as_ref()
+            .ok_or(GtexError::MissingTpmMatrix)?;
+        let query = self.tpm_row(tpm_matrix, gene)?;
+        let Some(profile) = Profile::new(&tpm_matrix.row(query), options) else {
+            return Ok(Vec::new());
+        };
+
+        let mut neighbours: Vec<CoexpressedGene> = tpm_matrix
+            .gene_ids()
+            .iter()
+            .enumerate()
+            .filter(|&(position, _)| position != query)
+            .filter_map(|(position, id)| {
+                let other = Profile::new(&tpm_matrix.row(position), options)?;
+                Some(CoexpressedGene {
+                    gene: &self.results[id],
+                    correlation: profile.correlation(&other),
+                })
+            })
+            .collect();
+        neighbours.sort_by(|a, b| {
+            b.correlation
+                .total_cmp(&a.correlation)
+                .then_with(|| a.gene.id.cmp(&b.gene.id))
+        });
+        neighbours.truncate(options.top_k);
+        Ok(neighbours)
+    }
+
+    /// Correlates every pair of `genes` (IDs or symbols, as in `coexpressed_genes`),
+    /// keeping the pairs whose absolute correlation reaches `options.min_abs_correlation`.
+    ///
+    /// Edges follow the order of `genes`, with `gene_a` listed before `gene_b`.
+    pub fn coexpression_edges(
+        &self,
+        genes: &[&str],
+        options: &CoexpressionOptions,
+    ) -> Result<Vec<CoexpressionEdge<'_>>, GtexError> {
+        let tpm_matrix = self
+            .tpm_matrix
+            .as_ref()
+            .ok_or(GtexError::MissingTpmMatrix)?;
+        let mut profiles = Vec::with_capacity(genes.len());
+        for gene in genes {
+            let position = self.tpm_row(tpm_matrix, gene)?;
+            let id = &tpm_matrix.gene_ids()[position];
+            if let Some(profile) = Profile::new(&tpm_matrix.row(position), options) {
+                profiles.push((&self.results[id], profile));
+            }
+        }
+
+        let mut edges = Vec::new();
+        for (i, (gene_a, profile_a)) in profiles.iter().enumerate() {
+            for (gene_b, profile_b) in &profiles[i + 1..] {
+                let correlation = profile_a.correlation(profile_b);
+                if correlation.abs() >= options.min_abs_correlation {
+                    edges.push(CoexpressionEdge {
+                        gene_a,
+                        gene_b,
+                        correlation,
+                    });
+                }
+            }
+        }
+        Ok(edges)
+    }
+
+    /// Writes the edges of `coexpression_edges` as a tab-separated edge list with a
+    /// `gene_a`, `gene_b` and `correlation` header, returning the number of edges.
+    pub fn write_coexpression_edges<W: Write>(
+        &self,
+        genes: &[&str],
+        options: &CoexpressionOptions,
+        mut writer: W,
+    ) -> Result<usize, GtexError> {
+        let edges = self.coexpression_edges(genes, options)?;
+        writeln!(writer, "gene_a\tgene_b\tcorrelation")?;
+        for edge in &edges {
+            writeln!(
+                writer,
+                "{}\t{}\t{}",
+                edge.gene_a.id, edge.gene_b.id, edge.correlation
+            )?;
+        }
+        writer.flush()?;
+        Ok(edges.len())
+    }
+
+    /// Correlates every pair of tissues across the genes of the TPM matrix, or across `genes`
+    /// (IDs or symbols, as in `coexpressed_genes`) such as a set of highly variable genes.
+    ///
+    /// Tissues follow `GCTMetadata::get_tissue_names`. The matrix can then be clustered with
+    /// `TissueMatrix::cluster`, which works on the distances `1 - r`.
+    pub fn tissue_correlations(
+        &self,
+        genes: Option<&[&str]>,
+        method: CorrelationMethod,
+        log_transform: bool,
+    ) -> Result<TissueMatrix, GtexError> {
+        let tpm_matrix = self
+            .tpm_matrix
+            .as_ref()
+            .ok_or(GtexError::MissingTpmMatrix)?;
+        let columns: Vec<Vec<TPMValue>> = match genes {
+            None => (0..tpm_matrix.num_tissues())
+                .map(|tissue| tpm_matrix.column(tissue).to_vec())
+                .collect(),
+            Some(genes) => {
+                let rows = genes
+                    .iter()
+                    .map(|gene| self.tpm_row(tpm_matrix, gene))
+                    .collect::<Result<Vec<usize>, GtexError>>()?;
+                (0..tpm_matrix.num_tissues())
+                    .map(|tissue| {
+                        let column = tpm_matrix.column(tissue);
+                        rows.iter().map(|&row| column[row]).collect()
+                    })
+                    .collect()
+            }
+        };
+        Ok(TissueMatrix::correlations(
+            self.metadata.get_tissue_names().to_vec(),
+            &columns,
+            method,
+            log_transform,
+        ))
+    }
+
+    /// Runs a principal component analysis of the tissues over the log2(TPM + 1) of every
+    /// gene of the TPM matrix, keeping the first `num_components` components.
+    ///
+    /// The result holds the tissue coordinates, the explained variance of each component and
+    /// the gene loadings, and can be saved with `Pca::save_json`.
+    pub fn pca(&self, num_components: usize) -> Result<Pca, GtexError> {
+        let tpm_matrix = self
+            .tpm_matrix
+            .as_ref()
+            .ok_or(GtexError::MissingTpmMatrix)?;
+        let columns: Vec<Vec<f64>> = (0..tpm_matrix.num_tissues())
+            .map(|tissue| log_column(tpm_matrix.column(tissue)))
+            .collect();
+        Ok(Pca::from_columns(
+            self.metadata.get_tissue_names().to_vec(),
+            tpm_matrix.gene_ids().to_vec(),
+            &columns,
+            num_components,
+        ))
+    }
+
+    // Row of `gene` in the TPM matrix, looked up by ID and then by symbol
+    fn tpm_row(&self, tpm_matrix: &TpmMatrix, gene: &str) -> Result<usize, GtexError> {
+        let id = match self.results.get(gene) {
+            Some(dge) => &dge.id,
+            None => match self.by_symbol(gene).first() {
+                Some(dge) => &dge.id,
+                None => return Err(GtexError::UnknownGene(gene.to_string())),
+            },
+        };
+        tpm_matrix
+            .gene_position(id)
+            .ok_or_else(|| GtexError::UnknownGene(gene.to_string()))
+    }
+
+    /// Returns the `top_n` genes most variable across tissues under `method`, most variable
+    /// first.
+    ///
+    /// Scores reuse the mean and SD kept on each `DGEResult` while loading, so no TPM matrix
+    /// is needed. Genes not expressed, or with the same TPM in every tissue, are left out.
+    pub fn highly_variable_genes(
+        &self,
+        method: VariabilityMethod,
+        top_n: usize,
+    ) -> Vec<VariableGene<'_>> {
+        select_variable_genes(self.results.values(), method, top_n)
+    }
+
+    /// Returns every gene with the symbol `symbol` (e.g. "DDX11L1"), sorted by ID.
+    ///
+    /// Symbols are not unique, so ambiguous symbols return all the matching genes.
+    pub fn by_symbol(&self, symbol: &str) -> Vec<&DGEResult> {
+        self.lookup(&self.symbol_index, symbol)
+    }
+
+    /// Returns the genes whose Ensembl ID without version matches `id`, sorted by ID.
+    ///
+    /// `id` may be given with or without version: "ENSG00000223972" and "ENSG00000223972.5"
+    /// both find "ENSG00000223972.5".
+    pub fn by_ensembl_base(&self, id: &str) -> Vec<&DGEResult> {
+        self.lookup(&self.ensembl_base_index, ensembl_base(id))
+    }
+
+    fn lookup(&self, index: &HashMap<String, Vec<String>>, key: &str) -> Vec<&DGEResult> {
+        index
+            .get(key)
+            .map(|ids| ids.iter().map(|id| &self.results[id]).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns how many genes have each `ExpressionStatus`.
+    ///
+    /// Genes counted as `NotExpressed` or `Constant` were not testable, as opposed to tested
+    /// genes without any differentially expressed tissue.
+    pub fn get_status_counts(&self) -> HashMap<ExpressionStatus, usize> {
+        let mut counts = HashMap::new();
+        for dge in self.results.values() {
+            *counts.entry(dge.status).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Returns the genes with specificity indices, the most tissue-specific first.
+    ///
+    /// Empty unless the summary was loaded with `GtexSummaryLoader::with_specificity`.
+    pub fn rank_by_specificity(&self, metric: SpecificityMetric) -> Vec<&DGEResult> {
+        let mut ranked: Vec<(&DGEResult, &SpecificityIndices)> = self
+            .results
+            .values()
+            .filter_map(|dge| dge.specificity.as_ref().map(|indices| (dge, indices)))
+            .collect();
+        ranked.sort_by(|(a, a_indices), (b, b_indices)| {
+            a_indices
+                .cmp_specificity(b_indices, metric)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        ranked.into_iter().map(|(dge, _)| dge).collect()
+    }
+
+    /// Returns the TPM matrix, if the summary was loaded with
+    /// `GtexSummaryLoader::with_tpm_matrix`.
+    pub fn get_tpm_matrix(&self) -> Option<&TpmMatrix> {
+        self.tpm_matrix.as_ref()
+    }
+
+    /// Flags the genes stably expressed in every tissue under `criteria`, replacing the
+    /// previous candidates.
+    ///
+    /// Uses the statistics kept on each `DGEResult`, so it also works on cached summaries.
+    pub fn classify_housekeeping(&mut self, criteria: &HousekeepingCriteria) {
+        self.housekeeping = find_housekeeping_genes(self.results.values(), criteria);
+    }
+
+    /// Returns the housekeeping candidates ranked by stability, empty unless the summary
+    /// was loaded with `GtexSummaryLoader::with_housekeeping` or `classify_housekeeping`
+    /// was called.
+    pub fn get_housekeeping_genes(&self) -> &[HousekeepingGene] {
+        &self.housekeeping
+    }
+
+    /// Returns the per-tissue median, mean, IQR and sample counts of a summary loaded with
+    /// `GtexSummaryLoader::load_samples`.
+    pub fn get_sample_aggregates(&self) -> Option<&SampleAggregates> {
+        self.sample_aggregates.as_ref()
+    }
+
+    /// Returns the TPM of `gene_id` in `tissue`.
+    ///
+    /// `None` if the TPM matrix was not kept, or the gene or tissue is not in it.
+    pub fn get_tpm(&self, gene_id: &str, tissue: &str) -> Option<TPMValue> {
+        self.tpm_matrix.as_ref()?.get(gene_id, tissue)
+    }
+
+    /// Returns the method used to compute the per-tissue scores of the results.
+    pub fn get_scoring_method(&self) -> ScoringMethod {
+        self.scoring_method
+    }
+
+    /// Returns `true` if every row declared in the GCT dimension line was read.
+    ///
+    /// Loads limited by `n_max`, truncated files and files with extra rows are not complete.
+    pub fn is_complete(&self) -> bool {
+        self.row_count == RowCountStatus::Complete
+    }
+
+    /// Returns how the rows read compare with the row count of the GCT dimension line.
+    pub fn get_row_count_status(&self) -> RowCountStatus {
+        self.row_count
+    }
+
+    /// Returns the rows rejected while loading, always empty with `ErrorPolicy::Strict`.
+    pub fn get_validation_report(&self) -> &ValidationReport {
+        &self.validation
     }
 
     /// Returns a reference to the differential expression results.
@@ -34,44 +555,117 @@ impl GtexSummary {
 }
 
 impl GtexSummary {
-
     /// Save this `GtexSummary` to disk in a compact binary format using `bincode`.
     /// This is the fastest option for caching and reloading later.
     pub fn save_bincode<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
         let file = File::create(path)?;
-        let writer = BufWriter::new(file);
-        bincode::serialize_into(writer, self)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+        self.write_bincode(BufWriter::new(file))
+    }
+
+    /// Writes this `GtexSummary` in the `save_bincode` format to `writer`.
+    pub fn write_bincode<W: Write>(&self, writer: W) -> std::io::Result<()> {
+        bincode::serialize_into(writer, self).map_err(std::io::Error::other)
     }
 
     /// Load a `GtexSummary` from a `.bincode` file previously saved with `save_bincode`.
     pub fn load_bincode<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
         let file = File::open(path)?;
-        let reader = BufReader::new(file);
-        bincode::deserialize_from(reader)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+        Self::read_bincode(BufReader::new(file))
+    }
+
+    /// Reads a `GtexSummary` written with `write_bincode` from `reader`.
+    pub fn read_bincode<R: Read>(reader: R) -> std::io::Result<Self> {
+        let mut summary: Self = bincode::deserialize_from(reader).map_err(std::io::Error::other)?;
+        summary.build_indexes();
+        Ok(summary)
     }
 
     /// Save this `GtexSummary` to disk in human-readable JSON format.
     /// This is slower and larger than bincode but human readable.
+    ///
+    /// Tissues are written by name rather than by column index.
     pub fn save_json<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
         let file = File::create(path)?;
-        let writer = BufWriter::new(file);
-        serde_json::to_writer_pretty(writer, self)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+        self.write_json(BufWriter::new(file))
+    }
+
+    /// Writes this `GtexSummary` in the `save_json` format to `writer`.
+    pub fn write_json<W: Write>(&self, writer: W) -> std::io::Result<()> {
+        let mut value = serde_json::to_value(self).map_err(std::io::Error::other)?;
+        for_each_json_tissue(&mut value, |tissue| {
+            let name = tissue
+                .as_u64()
+                .and_then(|index| self.metadata.tissue_name(index as TissueIndex))
+                .ok_or_else(|| GtexError::UnknownTissue(tissue.to_string()))?;
+            *tissue = Value::String(name.to_string());
+            Ok(())
+        })?;
+        serde_json::to_writer_pretty(writer, &value).map_err(std::io::Error::other)
     }
 
     /// Load a `GtexSummary` from a `.json` file previously saved with `save_json`.
     pub fn load_json<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
         let file = File::open(path)?;
-        let reader = BufReader::new(file);
-        serde_json::from_reader(reader)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+        Self::read_json(BufReader::new(file))
     }
-}
 
+    /// Reads a `GtexSummary` written with `write_json` from `reader`.
+    pub fn read_json<R: Read>(reader: R) -> std::io::Result<Self> {
+        let mut value: Value = serde_json::from_reader(reader).map_err(std::io::Error::other)?;
+        let metadata: GCTMetadata =
+            serde_json::from_value(value["metadata"].clone()).map_err(std::io::Error::other)?;
+        for_each_json_tissue(&mut value, |tissue| {
+            let index = tissue
+                .as_str()
+                .and_then(|name| metadata.tissue_index(name))
+                .ok_or_else(|| GtexError::UnknownTissue(tissue.to_string()))?;
+            *tissue = Value::from(index);
+            Ok(())
+        })?;
+
+        let mut summary: Self = serde_json::from_value(value).map_err(std::io::Error::other)?;
+        summary.build_indexes();
+        Ok(summary)
+    }
+}
 
+// Applies `convert` to the tissue of every call in a summary serialized as JSON
+fn for_each_json_tissue(
+    summary: &mut Value,
+    mut convert: impl FnMut(&mut Value) -> Result<(), GtexError>,
+) -> Result<(), GtexError> {
+    let Some(results) = summary["results"].as_object_mut() else {
+        return Ok(());
+    };
+    for dge in results.values_mut() {
+        for field in ["up_regulated", "down_regulated"] {
+            if let Some(calls) = dge[field].as_array_mut() {
+                for call in calls {
+                    convert(&mut call["tissue"])?;
+                }
+            }
+        }
+        if let Some(suppressed) = dge["suppressed"].as_array_mut() {
+            for call in suppressed {
+                convert(&mut call["tissue"]["tissue"])?;
+            }
+        }
+        if let Some(tests) = dge["tissue_tests"].as_array_mut() {
+            for test in tests {
+                convert(&mut test["tissue"])?;
+                if !test["reference"].is_null() {
+                    convert(&mut test["reference"])?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
 
+// Strips the version suffix of an Ensembl ID ("ENSG00000223972.5" -> "ENSG00000223972")
+fn ensembl_base(id: &str) -> &str {
+    id.split_once('.').map_or(id, |(base, _)| base)
+}
 
 /// A loader for processing GTEx gene expression datasets
 ///
@@ -105,16 +699,144 @@ impl GtexSummary {
 pub struct GtexSummaryLoader {
     n_max: Option<usize>,
     dge_threshold: Option<ZScoreValue>,
+    threads: Option<usize>,
+    error_policy: ErrorPolicy,
+    scoring_method: ScoringMethod,
+    expression_gates: ExpressionGates,
+    compute_specificity: bool,
+    keep_tpm_matrix: bool,
+    differential_test: Option<DifferentialTest>,
+    tissue_collapse: Option<(TissueHierarchy, CollapseMethod)>,
+    tissue_filter: TissueFilter,
+    housekeeping: Option<HousekeepingCriteria>,
 }
 
+/// Number of lines read and parsed together when loading in parallel.
+const PARALLEL_CHUNK_SIZE: usize = 4096;
+
 impl GtexSummaryLoader {
     pub fn new(n_max: Option<usize>, dge_threshold: Option<ZScoreValue>) -> Self {
         Self {
             n_max,
             dge_threshold: dge_threshold.map(|z| z.abs()), //To make sure it is not negative
+            threads: None,
+            error_policy: ErrorPolicy::Strict,
+            scoring_method: ScoringMethod::ZScore,
+            expression_gates: ExpressionGates::default(),
+            compute_specificity: false,
+            keep_tpm_matrix: false,
+            differential_test: None,
+            tissue_collapse: None,
+            tissue_filter: TissueFilter::default(),
+            housekeeping: None,
         }
     }
 
+    /// Tests every gene of a sample-level load (`load_samples`) with `differential_test`,
+    /// storing the results in `DGEResult::tissue_tests`.
+    ///
+    /// The adjusted p-values and calls are set by `load_samples` once every gene is read;
+    /// `stream_samples` only yields raw p-values. Median files cannot be tested: `load_summary`
+    /// and `stream` fail with `GtexError::DifferentialTestWithoutSamples`.
+    pub fn with_differential_test(mut self, differential_test: DifferentialTest) -> Self {
+        self.differential_test = Some(differential_test);
+        self
+    }
+
+    /// Restricts the analysis to the tissues matching one of `patterns`, tissue names or globs
+    /// such as "Brain_*" (see `GCTMetadata::match_tissues`).
+    ///
+    /// The other tissues are dropped before any statistics, and the metadata of the summary
+    /// only lists the analysed tissues. Loading fails with `GtexError::UnknownTissue` if a
+    /// pattern matches no tissue.
+    pub fn with_included_tissues(mut self, patterns: &[&str]) -> Self {
+        self.tissue_filter.include = patterns.iter().map(|p| p.to_string()).collect();
+        self
+    }
+
+    /// Drops the tissues matching one of `patterns` before any statistics, e.g. the cell
+    /// lines ("Cells_*") and single-nucleus pseudo-bulks ("*_Mixed_Cell") that are not
+    /// comparable to bulk tissue.
+    ///
+    /// Exclusions apply after `with_included_tissues` and before `with_tissue_collapse`, and
+    /// patterns matching no tissue are ignored. For sample-level loads the patterns match
+    /// the tissues of the sample attributes, spelled like the median GCT columns (see
+    /// `load_samples`).
+    pub fn with_excluded_tissues(mut self, patterns: &[&str]) -> Self {
+        self.tissue_filter.exclude = patterns.iter().map(|p| p.to_string()).collect();
+        self
+    }
+
+    /// Analyses tissue groups instead of sub-tissues, e.g. a single "Brain" column in place of
+    /// the brain regions, grouping the tissues with `hierarchy`.
+    ///
+    /// The TPM values of the sub-tissues of a group are combined with `method` before any
+    /// scoring. Sample-level loads instead pool the samples of the sub-tissues, so the
+    /// group statistics and tests are computed on every sample of the group.
+    pub fn with_tissue_collapse(
+        mut self,
+        hierarchy: TissueHierarchy,
+        method: CollapseMethod,
+    ) -> Self {
+        self.tissue_collapse = Some((hierarchy, method));
+        self
+    }
+
+    /// Flags the housekeeping candidates meeting `criteria` once every gene is loaded,
+    /// available through `GtexSummary::get_housekeeping_genes`.
+    pub fn with_housekeeping(mut self, criteria: HousekeepingCriteria) -> Self {
+        self.housekeeping = Some(criteria);
+        self
+    }
+
+    /// Keeps the TPM values of the loaded genes in a column-major `TpmMatrix`, available
+    /// through `GtexSummary::get_tpm_matrix` and saved with the summary.
+    ///
+    /// The matrix takes genes x tissues x 4 bytes, e.g. about 16 MB for 59k genes and 68 tissues.
+    pub fn with_tpm_matrix(mut self, keep_tpm_matrix: bool) -> Self {
+        self.keep_tpm_matrix = keep_tpm_matrix;
+        self
+    }
+
+    /// Computes the tissue-specificity indices (tau, Gini, TSI, entropy, Q and SPM) of every
+    /// gene while loading, stored in `DGEResult::specificity`.
+    pub fn with_specificity(mut self, compute_specificity: bool) -> Self {
+        self.compute_specificity = compute_specificity;
+        self
+    }
+
+    /// Sets the expression-level gates an up-regulated tissue must also pass.
+    ///
+    /// Calls failing a gate are kept in `DGEResult::suppressed` with the gate that removed them.
+    pub fn with_expression_gates(mut self, expression_gates: ExpressionGates) -> Self {
+        self.expression_gates = expression_gates;
+        self
+    }
+
+    /// Sets how the per-tissue scores are computed; the default is `ScoringMethod::ZScore`.
+    pub fn with_scoring_method(mut self, scoring_method: ScoringMethod) -> Self {
+        self.scoring_method = scoring_method;
+        self
+    }
+
+    /// Sets how malformed rows are handled; the default is `ErrorPolicy::Strict`.
+    ///
+    /// In the non-strict policies the rejected rows are listed in the validation report of
+    /// the returned `GtexSummary`.
+    pub fn with_error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+
+    /// Parses and analyses the rows on a pool of `threads` workers, reading the file in chunks.
+    ///
+    /// Errors and duplicate IDs are still reported for the first offending row in file order.
+    /// Passing `0` uses one worker per available CPU.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
     /// `GtexSummaryLoader` method that performs the analysis on the gene expression data and
     /// returns a `GtexSummary` object with the results.
     ///
@@ -123,113 +845,563 @@ impl GtexSummaryLoader {
     ///
     /// # Returns
     /// A new instance of `GtexSummary`.
-    pub fn load_summary<B>(&self, data: B) -> io::Result<GtexSummary>
+    pub fn load_summary<B>(&self, data: B) -> Result<GtexSummary, GtexError>
     where
         B: BufRead,
     {
-        let mut lines = data.lines();
-        // (1) parse the metadata to get the number of columns
-        //   create the metadata
-        let metadata = GCTMetadata::from_lines(&mut lines)?;
+        self.load(self.stream(data)?)
+    }
 
-        // (2) parse the records
-        let parser = RowParser {
-            metadata: &metadata,
-        };
+    /// Loads a sample-level GCT file (one column per sample), grouping the samples by their
+    /// tissue in `attributes`.
+    ///
+    /// The analysis runs on the median TPM of each tissue, like a median GCT file, and the
+    /// per-tissue median, mean, IQR and sample counts are available through
+    /// `GtexSummary::get_sample_aggregates`. Tissues are sorted by name, and samples missing
+    /// from `attributes` are left out, so restricting the attributes with
+    /// `SampleAttributes::retain` recomputes the statistics on a subset of samples.
+    ///
+    /// Tissue names are spelled like the columns of the median GCT files ("Brain - Cortex"
+    /// becomes "Brain_Cortex") before the tissues are filtered, collapsed or grouped, so both
+    /// loaders produce the same names.
+    pub fn load_samples<B>(
+        &self,
+        data: B,
+        attributes: &SampleAttributes,
+    ) -> Result<GtexSummary, GtexError>
+    where
+        B: BufRead,
+    {
+        self.load(self.stream_samples(data, attributes)?)
+    }
 
+    fn load<B>(
+        &self,
+        (metadata, mut stream): (GCTMetadata, DGEResultStream<B>),
+    ) -> Result<GtexSummary, GtexError>
+    where
+        B: BufRead,
+    {
         let mut results = HashMap::new();
+        for dge in stream.by_ref() {
+            let dge = dge?;
+            results.insert(dge.id.to_string(), dge);
+        }
 
-        for (index, line) in lines.enumerate() {
-            // TODO: possibly use `n_max` here to break out
-            if let Some(max_index) = self.n_max {
-                if index == max_index {
-                    break;
-                }
-            }
+        // q-values need the p-values of every gene
+        if let (Some(differential_test), Some(_)) = (&self.differential_test, &stream.tests) {
+            correct_tests(&mut results, differential_test);
+        }
 
-            // Use the threshold passed or if None is passed use 2.0
-            let threshold_used = self.dge_threshold.unwrap_or(2.0);
+        let mut summary = GtexSummary::new(metadata, results);
+        summary.row_count = stream.row_count_status();
+        summary.scoring_method = self.scoring_method;
+        summary.tpm_matrix = stream.take_tpm_matrix();
+        summary.sample_aggregates = stream.take_sample_aggregates();
+        if let Some(criteria) = &self.housekeeping {
+            summary.classify_housekeeping(criteria);
+        }
+        summary.build_indexes();
+        summary.validation = stream.into_validation_report();
+        Ok(summary)
+    }
 
-            let dge = parser.parse_row(&line?, index, threshold_used)?;
+    /// Parses the metadata and returns it together with a lazy iterator over the gene results.
+    ///
+    /// Rows are read, parsed and analysed one at a time, applying the same `n_max`, threshold
+    /// and duplicate ID checks as `load_summary`. Rows allowed by the error policy are skipped
+    /// and recorded in the stream's validation report; the iterator stops after the first
+    /// error it yields.
+    pub fn stream<B>(&self, data: B) -> Result<(GCTMetadata, DGEResultStream<B>), GtexError>
+    where
+        B: BufRead,
+    {
+        self.stream_with(data, None)
+    }
 
-            // Check if the ID is already present
-            match results.entry(dge.id.to_string()) {
-                std::collections::hash_map::Entry::Occupied(_) => {
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::InvalidData,
-                        format!("Row with ID (Name) '{}' already exists", dge.id),
-                    ));
-                }
-                std::collections::hash_map::Entry::Vacant(entry) => {
-                    entry.insert(dge);
-                }
+    /// Like `stream`, for a sample-level GCT file whose samples are grouped by their tissue
+    /// in `attributes` (see `load_samples`).
+    ///
+    /// The returned metadata has one column per tissue.
+    pub fn stream_samples<B>(
+        &self,
+        data: B,
+        attributes: &SampleAttributes,
+    ) -> Result<(GCTMetadata, DGEResultStream<B>), GtexError>
+    where
+        B: BufRead,
+    {
+        self.stream_with(data, Some(attributes))
+    }
+
+    fn stream_with<B>(
+        &self,
+        data: B,
+        attributes: Option<&SampleAttributes>,
+    ) -> Result<(GCTMetadata, DGEResultStream<B>), GtexError>
+    where
+        B: BufRead,
+    {
+        let mut lines = data.lines().peekable();
+        // (1) parse the metadata to get the number of columns
+        //   create the metadata, picking the parser from the version line
+        let is_v1_3 = matches!(lines.peek(), Some(Ok(version)) if version.trim() == "#1.3");
+        let metadata = if is_v1_3 {
+            GCTMetadata::from_lines_v1_3(&mut lines)?
+        } else {
+            GCTMetadata::from_lines(&mut lines)?
+        };
+
+        let pool = match self.threads {
+            Some(threads) => Some(
+                ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .map_err(|e| GtexError::Io(io::Error::other(e)))?,
+            ),
+            None => None,
+        };
+        // Sample-level files are filtered and collapsed through the tissues of their samples,
+        // named like the columns of the median files
+        let renamed_attributes = attributes.map(SampleAttributes::with_gct_tissue_names);
+        let attributes = renamed_attributes.as_ref();
+        let filtered_attributes = match attributes {
+            Some(attributes) if !self.tissue_filter.is_empty() => {
+                Some(self.tissue_filter.filter_samples(attributes)?)
             }
-        }
+            _ => None,
+        };
+        let attributes = filtered_attributes.as_ref().or(attributes);
+        let grouped_attributes = match (attributes, &self.tissue_collapse) {
+            (Some(attributes), Some((hierarchy, _))) => Some(hierarchy.group_samples(attributes)),
+            _ => None,
+        };
+        let grouping = grouped_attributes
+            .as_ref()
+            .or(attributes)
+            .map(|attributes| SampleGrouping::new(&metadata, attributes))
+            .transpose()?;
+        let selection = match attributes {
+            None if !self.tissue_filter.is_empty() => {
+                Some(SelectedColumns::new(&metadata, &self.tissue_filter)?)
+            }
+            _ => None,
+        };
+        let collapse = match (&grouping, &self.tissue_collapse) {
+            (None, Some((hierarchy, method))) => {
+                let selected = selection
+                    .as_ref()
+                    .map_or(&metadata, |selection| selection.metadata());
+                Some(CollapsedColumns::new(selected, hierarchy, *method))
+            }
+            _ => None,
+        };
+        let tests = match (&self.differential_test, &grouping) {
+            (Some(differential_test), Some(grouping)) => {
+                Some(SampleTests::new(differential_test, grouping.metadata())?)
+            }
+            (Some(_), None) => return Err(GtexError::DifferentialTestWithoutSamples),
+            (None, _) => None,
+        };
 
-        Ok(GtexSummary::new(metadata, results))
+        let stream = DGEResultStream {
+            lines,
+            metadata,
+            n_max: self.n_max,
+            // Use the threshold passed or if None is passed use 2.0
+            dge_threshold: self.dge_threshold.unwrap_or(2.0),
+            scoring_method: self.scoring_method,
+            expression_gates: self.expression_gates,
+            compute_specificity: self.compute_specificity,
+            index: 0,
+            reached_end: false,
+            seen_ids: HashMap::new(),
+            finished: false,
+            error_policy: self.error_policy,
+            report: ValidationReport::default(),
+            pool,
+            pending: VecDeque::new(),
+            tpm_matrix: self.keep_tpm_matrix.then(TpmMatrixBuilder::default),
+            aggregates: grouping
+                .as_ref()
+                .map(|_| SampleAggregatesBuilder::default()),
+            grouping,
+            tests,
+            selection,
+            collapse,
+        };
+        Ok((stream.metadata().clone(), stream))
     }
 }
 
-pub struct RowParser<'a> {
-    metadata: &'a GCTMetadata,
+/// Lazy iterator over the `DGEResult` of each row, returned by `GtexSummaryLoader::stream`.
+pub struct DGEResultStream<B: BufRead> {
+    lines: Peekable<Lines<B>>,
+    metadata: GCTMetadata,
+    n_max: Option<usize>,
+    dge_threshold: ZScoreValue,
+    scoring_method: ScoringMethod,
+    expression_gates: ExpressionGates,
+    compute_specificity: bool,
+    index: usize,
+    reached_end: bool,
+    /// Line number of each gene ID seen so far.
+    seen_ids: HashMap<String, usize>,
+    finished: bool,
+    error_policy: ErrorPolicy,
+    report: ValidationReport,
+    pool: Option<ThreadPool>,
+    /// Parsed rows of the current chunk, in file order.
+    pending: VecDeque<Result<ParsedRow, GtexError>>,
+    /// TPM values of the rows returned so far, if kept.
+    tpm_matrix: Option<TpmMatrixBuilder>,
+    /// Tissue of each sample column, when streaming a sample-level file.
+    grouping: Option<SampleGrouping>,
+    /// Per-tissue statistics of the rows returned so far, when streaming a sample-level file.
+    aggregates: Option<SampleAggregatesBuilder>,
+    /// Sample-level tests run on each row, when streaming a sample-level file.
+    tests: Option<SampleTests>,
+    /// Tissue columns kept by the loader's include and exclude lists, when set.
+    selection: Option<SelectedColumns>,
+    /// Tissue groups the columns are collapsed into, when the loader collapses tissues.
+    collapse: Option<CollapsedColumns>,
 }
 
-impl RowParser<'_> {
-    pub fn parse_row(
-        &self,
-        line: &str,
-        index: usize,
-        dge_threshold: ZScoreValue,
-    ) -> io::Result<DGEResult> {
-        // anyhow::bail!("I cannot proceed: {reason:?}")
-        let (id, symbol, tpms) = Self::separate_id_symbol_tpm(line)?;
+/// Line number, result, analysed TPM values and per-tissue statistics of a parsed row.
+type ParsedRow = (usize, DGEResult, Box<[TPMValue]>, Option<TissueStats>);
 
-        if tpms.len() != self.metadata.num_tissues {
-            return Err(Error::new(
-                ErrorKind::InvalidInput,
-                format!(
-                    "Invalid number of tpm values with respect to the header, row number {}.\nExpected values: {}, found: {}.",
-                    index + 4, self.metadata.num_tissues, tpms.len()
-                ),
-            ));
+impl<B: BufRead> DGEResultStream<B> {
+    /// Returns the metadata of the results: the file's, one column per tissue when streaming
+    /// a sample-level file, or one column per tissue group when collapsing tissues. Tissues
+    /// dropped by the loader's include and exclude lists are left out.
+    pub fn metadata(&self) -> &GCTMetadata {
+        match (&self.grouping, &self.collapse, &self.selection) {
+            (Some(grouping), _, _) => grouping.metadata(),
+            (None, Some(collapse), _) => collapse.metadata(),
+            (None, None, Some(selection)) => selection.metadata(),
+            (None, None, None) => &self.metadata,
         }
-
-        //create DGEResult
-        let dge_result = DGEResult::from_analysis(
-            id.to_string(),
-            symbol.to_string(),
-            &tpms,
-            self.metadata,
-            dge_threshold,
-        );
-        Ok(dge_result)
     }
 
-    // Splits a line into ID, Symbol, and TPM values
-    pub fn separate_id_symbol_tpm(content: &str) -> io::Result<(&str, &str, Box<[TPMValue]>)> {
-        let elems: Vec<&str> = content.split_whitespace().collect();
-        let id: &str = elems[0];
-        let symbol: &str = elems[1];
-        let tpms: Box<[TPMValue]> = elems[2..]
-            .iter()
-            .map(|elem| {
-                elem.parse::<TPMValue>().map_err(|_| {
-                    io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        format!("Invalid TPM value for gene ID {}: '{}'", id, elem),
-                    )
-                })
-            })
-            .collect::<Result<Vec<TPMValue>, io::Error>>()?
-            .into_boxed_slice();
-        Ok((id, symbol, tpms))
+    /// Returns the rows skipped so far under the loader's error policy.
+    pub fn validation_report(&self) -> &ValidationReport {
+        &self.report
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::io::Cursor;
+    /// Compares the rows read so far with the row count declared in the metadata.
+    ///
+    /// Rejected rows count as read. Until the end of the file is reached the status is
+    /// `RowCountStatus::Partial`.
+    pub fn row_count_status(&self) -> RowCountStatus {
+        if self.reached_end {
+            RowCountStatus::compare(self.metadata.num_rows, self.index)
+        } else {
+            RowCountStatus::Partial { rows: self.index }
+        }
+    }
+
+    /// Returns the TPM matrix of the rows returned so far, if the loader keeps it.
+    ///
+    /// Rows returned afterwards start a new matrix.
+    pub fn take_tpm_matrix(&mut self) -> Option<TpmMatrix> {
+        let tissue_names = self.metadata().get_tissue_names().to_vec();
+        let builder = self.tpm_matrix.as_mut()?;
+        Some(std::mem::take(builder).build(tissue_names))
+    }
+
+    /// Returns the per-tissue statistics of the rows returned so far, when streaming a
+    /// sample-level file.
+    ///
+    /// Rows returned afterwards start new statistics.
+    pub fn take_sample_aggregates(&mut self) -> Option<SampleAggregates> {
+        let builder = self.aggregates.as_mut()?;
+        let grouping = self.grouping.as_ref()?;
+        Some(std::mem::take(builder).build(grouping))
+    }
+
+    /// Consumes the stream and returns the rows skipped under the loader's error policy.
+    pub fn into_validation_report(self) -> ValidationReport {
+        self.report
+    }
+
+    fn next_result(&mut self) -> Option<Result<DGEResult, GtexError>> {
+        if self.pending.is_empty() {
+            self.read_chunk();
+        }
+        let (line_number, dge, tpms, stats) = match self.pending.pop_front()? {
+            Ok(parsed) => parsed,
+            Err(e) => return Some(Err(e)),
+        };
+
+        // Check if the ID is already present
+        match self.seen_ids.entry(dge.id.to_string()) {
+            Entry::Occupied(entry) => {
+                return Some(Err(GtexError::DuplicateGeneId {
+                    id: dge.id,
+                    first_line: *entry.get(),
+                    second_line: line_number,
+                }));
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(line_number);
+            }
+        }
+        if let Some(tpm_matrix) = &mut self.tpm_matrix {
+            tpm_matrix.push(&dge.id, &tpms);
+        }
+        if let (Some(aggregates), Some(stats)) = (&mut self.aggregates, &stats) {
+            aggregates.push(&dge.id, stats);
+        }
+        Some(Ok(dge))
+    }
+
+    // Reads the next lines (one, or a whole chunk with a pool) and parses them into `pending`
+    fn read_chunk(&mut self) {
+        let chunk_size = if self.pool.is_some() {
+            PARALLEL_CHUNK_SIZE
+        } else {
+            1
+        };
+
+        let mut chunk = Vec::with_capacity(chunk_size);
+        let mut read_error = None;
+        while chunk.len() < chunk_size {
+            if let Some(max_index) = self.n_max {
+                if self.index == max_index {
+                    break;
+                }
+            }
+            match self.lines.next() {
+                Some(Ok(line)) => chunk.push((self.index, line)),
+                Some(Err(e)) => {
+                    read_error = Some(e);
+                    break;
+                }
+                None => {
+                    self.reached_end = true;
+                    break;
+                }
+            }
+            self.index += 1;
+        }
+
+        let parser = RowParser {
+            metadata: &self.metadata,
+            scoring_method: self.scoring_method,
+            expression_gates: &self.expression_gates,
+            compute_specificity: self.compute_specificity,
+            grouping: self.grouping.as_ref(),
+            tests: self.tests.as_ref(),
+            selection: self.selection.as_ref(),
+            collapse: self.collapse.as_ref(),
+        };
+        let dge_threshold = self.dge_threshold;
+        let parse = |(index, line): &(usize, String)| {
+            let line_number = index + parser.header_lines() + 1;
+            parser
+                .parse_row(line, *index, dge_threshold)
+                .map(|(dge, tpms, stats)| (line_number, dge, tpms, stats))
+        };
+
+        let parsed: Vec<Result<ParsedRow, GtexError>> = match &self.pool {
+            Some(pool) => pool.install(|| chunk.par_iter().map(parse).collect()),
+            None => chunk.iter().map(parse).collect(),
+        };
+        self.pending.extend(parsed);
+        if let Some(e) = read_error {
+            self.pending.push_back(Err(GtexError::Io(e)));
+        }
+    }
+}
+
+impl<B: BufRead> Iterator for DGEResultStream<B> {
+    type Item = Result<DGEResult, GtexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        loop {
+            let next = self.next_result();
+            if let Some(Err(e)) = &next {
+                // Skip the row if the policy allows it and the error refers to a single row
+                if self.error_policy.allows_skip(self.report.rejected.len())
+                    && self.report.record(e)
+                {
+                    continue;
+                }
+            }
+            if !matches!(next, Some(Ok(_))) {
+                self.finished = true;
+            }
+            return next;
+        }
+    }
+}
+
+/// ID, row annotations and TPM values of a single GCT row.
+type AnnotatedRow<'a> = (&'a str, Vec<&'a str>, Box<[TPMValue]>);
+
+/// Result, analysed TPM values and per-tissue statistics of a single row.
+type AnalysedRow = (DGEResult, Box<[TPMValue]>, Option<TissueStats>);
+
+/// ID, symbol and TPM values of a single GCT 1.2 row.
+type SymbolRow<'a> = (&'a str, &'a str, Box<[TPMValue]>);
+
+pub struct RowParser<'a> {
+    metadata: &'a GCTMetadata,
+    scoring_method: ScoringMethod,
+    expression_gates: &'a ExpressionGates,
+    compute_specificity: bool,
+    grouping: Option<&'a SampleGrouping>,
+    tests: Option<&'a SampleTests>,
+    selection: Option<&'a SelectedColumns>,
+    collapse: Option<&'a CollapsedColumns>,
+}
+
+impl RowParser<'_> {
+    // Parses and analyses a row, also returning the analysed TPM values and, for sample-level
+    // rows, the per-tissue statistics
+    pub fn parse_row(
+        &self,
+        line: &str,
+        index: usize,
+        dge_threshold: ZScoreValue,
+    ) -> Result<AnalysedRow, GtexError> {
+        let line_number = index + self.header_lines() + 1;
+        let (id, annotations, tpms) = if self.metadata.is_v1_3() {
+            let num_annotations = self.metadata.row_annotation_names.len();
+            Self::separate_id_annotations_tpm(line, num_annotations, line_number)?
+        } else {
+            let (id, symbol, tpms) = Self::separate_id_symbol_tpm(line, line_number)?;
+            (id, vec![symbol], tpms)
+        };
+
+        if tpms.len() != self.metadata.num_tissues {
+            return Err(GtexError::RowLengthMismatch {
+                gene_id: id.to_string(),
+                line: line_number,
+                expected: self.metadata.num_tissues,
+                found: tpms.len(),
+            });
+        }
+
+        // The first row annotation (Description in GCT 1.2) is used as the gene symbol
+        let symbol = annotations.first().copied().unwrap_or_default();
+
+        // Excluded tissues are dropped before any statistics
+        let (metadata, tpms) = match self.selection {
+            Some(selection) => (selection.metadata(), selection.select(&tpms)),
+            None => (self.metadata, tpms),
+        };
+
+        let tissue_tests = match (self.grouping, self.tests) {
+            (Some(grouping), Some(tests)) => tests.run(grouping, &tpms),
+            _ => Vec::new(),
+        };
+
+        // Sample-level rows are analysed on the median of each tissue, and collapsed rows on
+        // the combined value of each tissue group
+        let stats = self.grouping.map(|grouping| grouping.aggregate(&tpms));
+        let (metadata, tpms) = match (self.grouping, &stats, self.collapse) {
+            (Some(grouping), Some(stats), _) => (grouping.metadata(), stats.median.clone()),
+            (None, _, Some(collapse)) => (collapse.metadata(), collapse.collapse(&tpms)),
+            _ => (metadata, tpms),
+        };
+
+        //create DGEResult
+        let mut dge_result = DGEResult::new(id.to_string(), symbol.to_string());
+        dge_result.tissue_tests = tissue_tests;
+        dge_result.perform_scored_analysis(&tpms, metadata, dge_threshold, self.scoring_method);
+        dge_result.apply_expression_gates(&tpms, metadata, self.expression_gates);
+        if self.compute_specificity {
+            dge_result.specificity = SpecificityIndices::from_tpms(&tpms);
+        }
+        if self.metadata.is_v1_3() {
+            dge_result.annotations = annotations.iter().map(|s| s.to_string()).collect();
+        }
+        Ok((dge_result, tpms, stats))
+    }
+
+    // Number of lines preceding the first gene row
+    fn header_lines(&self) -> usize {
+        3 + self.metadata.column_annotations.len()
+    }
+
+    // Splits a tab-delimited GCT 1.3 line into ID, `num_annotations` row annotations, and
+    // TPM values. Rows too short to hold the annotations yield no TPM values.
+    pub fn separate_id_annotations_tpm(
+        content: &str,
+        num_annotations: usize,
+        line: usize,
+    ) -> Result<AnnotatedRow<'_>, GtexError> {
+        let elems = split_tab_fields(content);
+        let id: &str = elems.first().copied().unwrap_or_default();
+        let tpm_start = elems.len().min(num_annotations + 1);
+        let annotations: Vec<&str> = elems[1.min(tpm_start)..tpm_start].to_vec();
+        let tpms = Self::parse_tpms(id, &elems[tpm_start..], tpm_start, line)?;
+        Ok((id, annotations, tpms))
+    }
+
+    // Splits a line into ID, Symbol, and TPM values
+    pub fn separate_id_symbol_tpm(content: &str, line: usize) -> Result<SymbolRow<'_>, GtexError> {
+        let elems: Vec<&str> = content.split_whitespace().collect();
+        let id: &str = elems.first().copied().unwrap_or_default();
+        let symbol: &str = elems.get(1).copied().unwrap_or_default();
+        let tpm_start = elems.len().min(2);
+        let tpms = Self::parse_tpms(id, &elems[tpm_start..], tpm_start, line)?;
+        Ok((id, symbol, tpms))
+    }
+
+    // Parses the TPM fields, `offset` being the 0-based column of the first one
+    fn parse_tpms(
+        id: &str,
+        fields: &[&str],
+        offset: usize,
+        line: usize,
+    ) -> Result<Box<[TPMValue]>, GtexError> {
+        fields
+            .iter()
+            .enumerate()
+            .map(|(position, elem)| {
+                elem.parse::<TPMValue>().map_err(|_| GtexError::InvalidTpm {
+                    gene_id: id.to_string(),
+                    line,
+                    column: offset + position + 1,
+                    raw: elem.to_string(),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::nonminimal_bool,
+    clippy::to_string_in_format_args,
+    clippy::useless_vec
+)]
+mod tests {
+    use super::*;
+    use crate::expression_analysis::{
+        Contrast, CorrelationMethod, Linkage, Regulation, StatisticalTest,
+    };
+    use std::io::Cursor;
+
+    // Saves and reloads `summary` in the bincode format, in memory
+    fn bincode_round_trip(summary: &GtexSummary) -> std::io::Result<GtexSummary> {
+        let mut buffer = Vec::new();
+        summary.write_bincode(&mut buffer)?;
+        GtexSummary::read_bincode(buffer.as_slice())
+    }
+
+    // Saves and reloads `summary` in the JSON format, in memory, also returning the JSON
+    fn json_round_trip(summary: &GtexSummary) -> std::io::Result<(String, GtexSummary)> {
+        let mut buffer = Vec::new();
+        summary.write_json(&mut buffer)?;
+        let cached = GtexSummary::read_json(buffer.as_slice())?;
+        Ok((String::from_utf8_lossy(&buffer).into_owned(), cached))
+    }
 
     #[test]
     fn test_gtex_summary_from_reader() {
@@ -254,7 +1426,7 @@ mod tests {
     #[test]
     fn test_separate_id_symbol_tpm() {
         let content = "Gene1 Symbol1 1.2 3.4 5.6";
-        let output = RowParser::separate_id_symbol_tpm(content);
+        let output = RowParser::separate_id_symbol_tpm(content, 4);
 
         assert!(!output.is_err(), "It should not return an Err");
 
@@ -339,4 +1511,984 @@ mod tests {
         assert!(unwr.to_string().contains("already exists"));
         Ok(())
     }
+
+    #[test]
+    fn test_stream_results() -> Result<(), Box<dyn std::error::Error>> {
+        let input = "v1.2\n3 2\nID SYMBOL T1 T2\nGene1 Symbol1 1.2 3.4\nGene2 Symbol2 1.2 3.4\nGene3 Symbol3 1.2 3.4";
+        let summary_loader = GtexSummaryLoader::new(Some(2), None);
+        let (metadata, stream) = summary_loader.stream(Cursor::new(input))?;
+
+        assert_eq!(metadata.num_tissues, 2);
+        let ids = stream
+            .map(|dge| dge.map(|dge| dge.id))
+            .collect::<Result<Vec<String>, GtexError>>()?;
+        assert_eq!(ids, ["Gene1", "Gene2"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_stops_after_duplicated_id() -> Result<(), Box<dyn std::error::Error>> {
+        let input = "v1.2\n3 2\nID SYMBOL T1 T2\nGene1 Symbol1 1.2 3.4\nGene1 Symbol1 1.2 3.4\nGene3 Symbol3 1.2 3.4";
+        let summary_loader = GtexSummaryLoader::new(None, None);
+        let (_, mut stream) = summary_loader.stream(Cursor::new(input))?;
+
+        assert!(stream.next().unwrap().is_ok());
+        assert!(stream
+            .next()
+            .unwrap()
+            .unwrap_err()
+            .to_string()
+            .contains("already exists"));
+        assert!(stream.next().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parallel_load_matches_sequential() -> Result<(), Box<dyn std::error::Error>> {
+        let file_path = "data/GTEx_RNASeq_gene_median_tpm_HEAD.gct";
+        let sequential = GtexSummaryLoader::new(None, None)
+            .load_summary(BufReader::new(File::open(file_path)?))?;
+        let parallel = GtexSummaryLoader::new(None, None)
+            .with_threads(4)
+            .load_summary(BufReader::new(File::open(file_path)?))?;
+
+        assert_eq!(parallel.get_results().len(), sequential.get_results().len());
+        for (id, dge) in sequential.get_results() {
+            let other = &parallel.get_results()[id];
+            assert_eq!(other.up_regulated.len(), dge.up_regulated.len());
+            assert_eq!(other.down_regulated.len(), dge.down_regulated.len());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parallel_reports_first_bad_row() {
+        let input = [
+            "v1.2\n4 2\nID SYMBOL T1 T2",
+            "Gene1 Symbol1 1.2 3.4",
+            "Gene2 Symbol2 1.2 x",
+            "Gene3 Symbol3 1.2",
+            "Gene4 Symbol4 1.2 3.4",
+        ];
+        let summary_loader = GtexSummaryLoader::new(None, None).with_threads(2);
+        let cursor = Cursor::new(input.join("\n").into_bytes());
+        let error = summary_loader.load_summary(cursor).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Invalid TPM value for gene ID Gene2"));
+        assert!(error.to_string().contains("row number 5"));
+    }
+
+    #[test]
+    fn test_parallel_detects_duplicated_id() {
+        let input = [
+            "v1.2\n3 2\nID SYMBOL T1 T2",
+            "Gene1 Symbol1 1.2 3.4",
+            "Gene2 Symbol2 1.2 3.4",
+            "Gene1 Symbol1 1.2 3.4",
+        ];
+        let summary_loader = GtexSummaryLoader::new(None, None).with_threads(2);
+        let cursor = Cursor::new(input.join("\n").into_bytes());
+        let error = summary_loader.load_summary(cursor).unwrap_err();
+        assert!(error.to_string().contains("'Gene1' already exists"));
+    }
+
+    #[test]
+    fn test_error_variants_carry_positions() {
+        let load = |rows: &[&str]| {
+            let input = format!("v1.2\n3 2\nID SYMBOL T1 T2\n{}", rows.join("\n"));
+            GtexSummaryLoader::new(None, None).load_summary(Cursor::new(input))
+        };
+
+        match load(&["Gene1 Symbol1 1.2 3.4", "Gene2 Symbol2 1.2 n/a"]).unwrap_err() {
+            GtexError::InvalidTpm {
+                gene_id,
+                line,
+                column,
+                raw,
+            } => {
+                assert_eq!(gene_id, "Gene2");
+                assert_eq!((line, column), (5, 4));
+                assert_eq!(raw, "n/a");
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+
+        assert!(matches!(
+            load(&["Gene1 Symbol1 1.2"]).unwrap_err(),
+            GtexError::RowLengthMismatch {
+                line: 4,
+                expected: 2,
+                found: 1,
+                ..
+            }
+        ));
+
+        assert!(matches!(
+            load(&[
+                "Gene1 Symbol1 1.2 3.4",
+                "Gene2 Symbol2 1.2 3.4",
+                "Gene1 Symbol1 1.2 3.4"
+            ])
+            .unwrap_err(),
+            GtexError::DuplicateGeneId {
+                first_line: 4,
+                second_line: 6,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_empty_row_is_a_length_mismatch() {
+        let input = "v1.2\n2 2\nID SYMBOL T1 T2\nGene1 Symbol1 1.2 3.4\n\nGene2 Symbol2 1.2 3.4";
+        let error = GtexSummaryLoader::new(None, None)
+            .load_summary(Cursor::new(input))
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            GtexError::RowLengthMismatch {
+                line: 5,
+                found: 0,
+                ..
+            }
+        ));
+    }
+
+    const CORRUPTED_INPUT: &str = "v1.2\n5 2\nID SYMBOL T1 T2\nGene1 Symbol1 1.2 3.4\nGene2 Symbol2 1.2 NaN?\nGene3 Symbol3 1.2\nGene1 Symbol1 1.2 3.4\nGene5 Symbol5 1.2 3.4";
+
+    #[test]
+    fn test_skip_and_record_policy() -> Result<(), Box<dyn std::error::Error>> {
+        let summary = GtexSummaryLoader::new(None, None)
+            .with_error_policy(ErrorPolicy::SkipAndRecord)
+            .load_summary(Cursor::new(CORRUPTED_INPUT))?;
+
+        assert_eq!(summary.get_results().len(), 2);
+        assert!(summary.get_results().contains_key("Gene5"));
+        let rejected = &summary.get_validation_report().rejected;
+        let lines: Vec<(usize, &str)> = rejected
+            .iter()
+            .map(|row| (row.line, row.gene_id.as_str()))
+            .collect();
+        assert_eq!(lines, [(5, "Gene2"), (6, "Gene3"), (7, "Gene1")]);
+        assert!(rejected[2].reason.contains("already exists"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_stop_after_policy() {
+        let loader = |max_errors| {
+            GtexSummaryLoader::new(None, None)
+                .with_error_policy(ErrorPolicy::StopAfter(max_errors))
+                .load_summary(Cursor::new(CORRUPTED_INPUT))
+        };
+
+        let summary = loader(3).unwrap();
+        assert_eq!(summary.get_validation_report().rejected.len(), 3);
+        assert!(matches!(
+            loader(2).unwrap_err(),
+            GtexError::DuplicateGeneId { second_line: 7, .. }
+        ));
+    }
+
+    #[test]
+    fn test_strict_policy_has_empty_report() -> Result<(), Box<dyn std::error::Error>> {
+        let summary =
+            GtexSummaryLoader::new(Some(1), None).load_summary(Cursor::new(CORRUPTED_INPUT))?;
+        assert!(summary.get_validation_report().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_count_status() -> Result<(), Box<dyn std::error::Error>> {
+        let load = |declared: usize, n_max: Option<usize>| {
+            let input = format!(
+                "v1.2\n{} 2\nID SYMBOL T1 T2\nGene1 Symbol1 1.2 3.4\nGene2 Symbol2 1.2 3.4",
+                declared
+            );
+            GtexSummaryLoader::new(n_max, None).load_summary(Cursor::new(input))
+        };
+
+        assert!(load(2, None)?.is_complete());
+        assert_eq!(
+            load(5, None)?.get_row_count_status(),
+            RowCountStatus::Truncated {
+                declared: 5,
+                found: 2
+            }
+        );
+        assert_eq!(
+            load(1, None)?.get_row_count_status(),
+            RowCountStatus::Overrun {
+                declared: 1,
+                found: 2
+            }
+        );
+        let partial = load(2, Some(1))?;
+        assert!(!partial.is_complete());
+        assert_eq!(
+            partial.get_row_count_status(),
+            RowCountStatus::Partial { rows: 1 }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejected_rows_count_as_read() -> Result<(), Box<dyn std::error::Error>> {
+        let summary = GtexSummaryLoader::new(None, None)
+            .with_error_policy(ErrorPolicy::SkipAndRecord)
+            .load_summary(Cursor::new(CORRUPTED_INPUT))?;
+        assert!(summary.is_complete());
+        Ok(())
+    }
+
+    #[test]
+    fn test_completeness_is_cached() -> Result<(), Box<dyn std::error::Error>> {
+        let input = "v1.2\n3 2\nID SYMBOL T1 T2\nGene1 Symbol1 1.2 3.4\nGene2 Symbol2 1.2 3.4";
+        let summary = GtexSummaryLoader::new(None, None).load_summary(Cursor::new(input))?;
+        assert!(!summary.is_complete());
+
+        for cached in [bincode_round_trip(&summary)?, json_round_trip(&summary)?.1] {
+            assert_eq!(
+                cached.get_row_count_status(),
+                RowCountStatus::Truncated {
+                    declared: 3,
+                    found: 2
+                }
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_robust_scoring_finds_second_tissue() -> Result<(), Box<dyn std::error::Error>> {
+        let input = "v1.2\n1 7\nID SYMBOL T1 T2 T3 T4 T5 T6 Testis\nGene1 Symbol1 1 2 2 3 3 8 1000";
+        let load = |scoring_method| {
+            GtexSummaryLoader::new(None, None)
+                .with_scoring_method(scoring_method)
+                .load_summary(Cursor::new(input))
+        };
+
+        let classic = load(ScoringMethod::ZScore)?;
+        let up: Vec<&str> = classic.get_results()["Gene1"]
+            .up_regulated
+            .iter()
+            .map(|tissue| tissue.tissue_name(&classic.metadata).unwrap())
+            .collect();
+        assert_eq!(up, ["Testis"]);
+
+        let robust = load(ScoringMethod::RobustZScore)?;
+        assert_eq!(robust.get_scoring_method(), ScoringMethod::RobustZScore);
+        let up: Vec<&str> = robust.get_results()["Gene1"]
+            .up_regulated
+            .iter()
+            .map(|tissue| tissue.tissue_name(&robust.metadata).unwrap())
+            .collect();
+        assert_eq!(up, ["T6", "Testis"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rank_by_specificity() -> Result<(), Box<dyn std::error::Error>> {
+        let input =
+            "v1.2\n3 3\nID SYMBOL T1 T2 T3\nBroad B 5 4 6\nSpecific S 0 0 50\nSilent Z 0 0 0";
+        let summary = GtexSummaryLoader::new(None, None)
+            .with_specificity(true)
+            .load_summary(Cursor::new(input))?;
+
+        assert!(summary.get_results()["Silent"].specificity.is_none());
+        for metric in [SpecificityMetric::Tau, SpecificityMetric::Entropy] {
+            let ranked: Vec<&str> = summary
+                .rank_by_specificity(metric)
+                .iter()
+                .map(|dge| dge.id.as_str())
+                .collect();
+            assert_eq!(ranked, ["Specific", "Broad"]);
+        }
+
+        let (_, cached) = json_round_trip(&summary)?;
+        assert_eq!(
+            cached.get_results()["Specific"].specificity,
+            summary.get_results()["Specific"].specificity
+        );
+
+        let without = GtexSummaryLoader::new(None, None).load_summary(Cursor::new(input))?;
+        assert!(without
+            .rank_by_specificity(SpecificityMetric::Tau)
+            .is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_lookup_by_symbol_and_ensembl_base() -> Result<(), Box<dyn std::error::Error>> {
+        let input = [
+            "v1.0\n3 3\n ID SYMBOL T1 T2 T3",
+            "ENSG01.5 Symbol1 1.2 3.4 5.6",
+            "ENSG02.1 Symbol2 2.2 4.4 6.6",
+            "ENSG03.2 Symbol2 2.2 4.4 6.6",
+        ];
+        let cursor = Cursor::new(input.join("\n").into_bytes());
+        let summary = GtexSummaryLoader::new(None, None).load_summary(cursor)?;
+
+        let ids = |results: Vec<&DGEResult>| -> Vec<String> {
+            results.iter().map(|dge| dge.id.to_string()).collect()
+        };
+        assert_eq!(ids(summary.by_symbol("Symbol1")), ["ENSG01.5"]);
+        assert_eq!(ids(summary.by_symbol("Symbol2")), ["ENSG02.1", "ENSG03.2"]);
+        assert!(summary.by_symbol("Missing").is_empty());
+        assert_eq!(ids(summary.by_ensembl_base("ENSG02")), ["ENSG02.1"]);
+        assert_eq!(ids(summary.by_ensembl_base("ENSG02.7")), ["ENSG02.1"]);
+
+        let cached = bincode_round_trip(&summary)?;
+        assert_eq!(ids(cached.by_symbol("Symbol2")), ["ENSG02.1", "ENSG03.2"]);
+        assert_eq!(ids(cached.by_ensembl_base("ENSG03")), ["ENSG03.2"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_genes_up_and_down_in_tissue() -> Result<(), Box<dyn std::error::Error>> {
+        let input = [
+            "v1.2\n3 5\nID SYMBOL Liver Lung T3 T4 T5",
+            "Gene1 Alb 90 1 1 1 1",
+            "Gene2 Apoa1 50 2 2 2 1",
+            "Gene3 Sftpc 0 80 10 10 10",
+        ];
+        let cursor = Cursor::new(input.join("\n").into_bytes());
+        let summary = GtexSummaryLoader::new(None, Some(0.7)).load_summary(cursor)?;
+
+        let up = summary.genes_up_in("Liver", None)?;
+        let ids: Vec<&str> = up.iter().map(|(dge, _)| dge.id.as_str()).collect();
+        assert_eq!(ids, ["Gene1", "Gene2"]);
+        assert!(up[0].1 >= up[1].1);
+        assert_eq!(summary.genes_up_in("Liver", Some(1))?.len(), 1);
+
+        let (json, cached) = json_round_trip(&summary)?;
+        assert!(json.contains("\"tissue\": \"Liver\""));
+        assert_eq!(
+            cached.get_results()["Gene1"].up_regulated,
+            summary.get_results()["Gene1"].up_regulated
+        );
+        assert_eq!(cached.genes_up_in("Liver", None)?.len(), 2);
+
+        let down = summary.genes_down_in("Liver", None)?;
+        assert_eq!(down.len(), 1);
+        assert_eq!(down[0].0.id, "Gene3");
+        assert!(summary.genes_up_in("T3", None)?.is_empty());
+
+        assert!(matches!(
+            summary.genes_up_in("Brain", None),
+            Err(GtexError::UnknownTissue(tissue)) if tissue == "Brain"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_tpm_matrix() -> Result<(), Box<dyn std::error::Error>> {
+        let input = [
+            "v1.2\n4 3\nID SYMBOL Liver Lung Testis",
+            "Gene1 Alb 90 1 2",
+            "Gene2 Sftpc 0 80 x",
+            "Gene3 Prm1 0 0 300",
+            "Gene4 Actb 50 60 70",
+        ];
+        let summary = GtexSummaryLoader::new(None, None)
+            .with_tpm_matrix(true)
+            .with_error_policy(ErrorPolicy::SkipAndRecord)
+            .with_threads(2)
+            .load_summary(Cursor::new(input.join("\n").into_bytes()))?;
+
+        let matrix = summary.get_tpm_matrix().unwrap();
+        assert_eq!(matrix.gene_ids(), ["Gene1", "Gene3", "Gene4"]);
+        assert_eq!(matrix.tissue_names(), ["Liver", "Lung", "Testis"]);
+        assert_eq!(matrix.tissue_values("Testis").unwrap(), [2.0, 300.0, 70.0]);
+        assert_eq!(matrix.gene_values("Gene4").unwrap(), [50.0, 60.0, 70.0]);
+        assert_eq!(summary.get_tpm("Gene1", "Liver"), Some(90.0));
+        assert_eq!(summary.get_tpm("Gene2", "Liver"), None);
+
+        let cached = bincode_round_trip(&summary)?;
+        assert_eq!(cached.get_tpm("Gene3", "Testis"), Some(300.0));
+
+        let without = GtexSummaryLoader::new(None, None)
+            .load_summary(Cursor::new(input[..2].join("\n").into_bytes()))?;
+        assert!(without.get_tpm_matrix().is_none());
+        assert_eq!(without.get_tpm("Gene1", "Liver"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_samples() -> Result<(), Box<dyn std::error::Error>> {
+        let input = [
+            "#1.2\n2 5\nName Description GTEX-1-A GTEX-1-B GTEX-2-A GTEX-2-B GTEX-3-A",
+            "Gene1 Alb 100 1 300 2 200",
+            "Gene2 Actb 50 60 40 70 30",
+        ];
+        let attributes = "SAMPID\tSMTSD\n\
+            GTEX-1-A\tLiver\n\
+            GTEX-1-B\tLung\n\
+            GTEX-2-A\tLiver\n\
+            GTEX-2-B\tLung\n\
+            GTEX-3-A\tLiver\n";
+        let mut attributes = SampleAttributes::from_reader(Cursor::new(attributes))?;
+        let loader = GtexSummaryLoader::new(None, Some(0.5)).with_tpm_matrix(true);
+
+        let summary = loader.load_samples(Cursor::new(input.join("\n")), &attributes)?;
+        assert_eq!(summary.metadata.get_tissue_names(), ["Liver", "Lung"]);
+        assert!(summary.is_complete());
+        assert_eq!(summary.get_tpm("Gene1", "Liver"), Some(200.0));
+        let liver = &summary.get_results()["Gene1"].up_regulated[0];
+        assert_eq!(liver.tissue_name(&summary.metadata), Some("Liver"));
+
+        let aggregates = summary.get_sample_aggregates().unwrap();
+        assert_eq!(aggregates.sample_counts(), [3, 2]);
+        assert_eq!(aggregates.sample_count("Lung"), Some(2));
+        assert_eq!(aggregates.median().get("Gene2", "Lung"), Some(65.0));
+        assert_eq!(aggregates.mean().get("Gene1", "Liver"), Some(200.0));
+        assert_eq!(aggregates.iqr().get("Gene1", "Liver"), Some(100.0));
+
+        // Donor subsets only change the samples each tissue is aggregated over
+        attributes.retain(|sample, _| !sample.starts_with("GTEX-3"));
+        let subset = loader.load_samples(Cursor::new(input.join("\n")), &attributes)?;
+        let aggregates = subset.get_sample_aggregates().unwrap();
+        assert_eq!(aggregates.sample_counts(), [2, 2]);
+        assert_eq!(aggregates.median().get("Gene1", "Liver"), Some(200.0));
+
+        let cached = bincode_round_trip(&subset)?;
+        let aggregates = cached.get_sample_aggregates().unwrap();
+        assert_eq!(aggregates.iqr().get("Gene2", "Lung"), Some(5.0));
+
+        let median_file =
+            GtexSummaryLoader::new(None, None).load_summary(Cursor::new(input.join("\n")))?;
+        assert!(median_file.get_sample_aggregates().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sample_tissues_named_like_median_columns() -> Result<(), Box<dyn std::error::Error>> {
+        let samples = "#1.2\n1 4\nName Description S1 S2 S3 S4\nGene1 GFAP 90 110 80 1";
+        let medians = "#1.2\n1 3\nName Description \
+            Brain_Anterior_cingulate_cortex_BA24 Brain_Cortex Liver\n\
+            Gene1 GFAP 80 100 1";
+        let mut attributes = SampleAttributes::default();
+        for (sample, tissue) in [
+            ("S1", "Brain - Cortex"),
+            ("S2", "Brain - Cortex"),
+            ("S3", "Brain - Anterior cingulate cortex (BA24)"),
+            ("S4", "Liver"),
+        ] {
+            attributes.insert(sample, tissue);
+        }
+
+        let loader = GtexSummaryLoader::new(None, Some(0.5));
+        let from_samples = loader.load_samples(Cursor::new(samples), &attributes)?;
+        let from_medians = loader.load_summary(Cursor::new(medians))?;
+        assert_eq!(
+            from_samples.metadata.get_tissue_names(),
+            from_medians.metadata.get_tissue_names()
+        );
+        let ids = |summary: &GtexSummary| -> Vec<String> {
+            summary
+                .genes_up_in("Brain_Cortex", None)
+                .unwrap()
+                .iter()
+                .map(|(dge, _)| dge.id.clone())
+                .collect()
+        };
+        assert_eq!(ids(&from_samples), ["Gene1"]);
+        assert_eq!(ids(&from_samples), ids(&from_medians));
+
+        let collapsed = loader
+            .with_tissue_collapse(TissueHierarchy::gtex(), CollapseMethod::Max)
+            .load_samples(Cursor::new(samples), &attributes)?;
+        assert_eq!(collapsed.metadata.get_tissue_names(), ["Brain", "Liver"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_collapse_tissues() -> Result<(), Box<dyn std::error::Error>> {
+        let input = [
+            "#1.2\n2 5\nName Description Brain_Cortex Liver Brain_Cerebellum Lung Brain_Spinal_cord",
+            "Gene1 Gfap 10 0 40 0 30",
+            "Gene2 Alb 1 90 1 2 1",
+        ]
+        .join("\n");
+        let loader = GtexSummaryLoader::new(None, Some(1.0))
+            .with_tpm_matrix(true)
+            .with_tissue_collapse(TissueHierarchy::gtex(), CollapseMethod::Max);
+
+        let summary = loader.load_summary(Cursor::new(input.clone()))?;
+        assert_eq!(
+            summary.metadata.get_tissue_names(),
+            ["Brain", "Liver", "Lung"]
+        );
+        assert_eq!(summary.get_tpm("Gene1", "Brain"), Some(40.0));
+        let brain = &summary.get_results()["Gene1"].up_regulated[0];
+        assert_eq!(brain.tissue_name(&summary.metadata), Some("Brain"));
+
+        let loader = GtexSummaryLoader::new(None, Some(1.0))
+            .with_tpm_matrix(true)
+            .with_tissue_collapse(TissueHierarchy::gtex(), CollapseMethod::Median);
+        let summary = loader.load_summary(Cursor::new(input.clone()))?;
+        assert_eq!(summary.get_tpm("Gene1", "Brain"), Some(30.0));
+
+        // Sample-level loads pool the samples of the sub-tissues
+        let mut attributes = SampleAttributes::default();
+        for (sample, tissue) in [
+            ("S1", "Brain - Cortex"),
+            ("S2", "Liver"),
+            ("S3", "Brain - Cerebellum"),
+            ("S4", "Lung"),
+            ("S5", "Brain - Spinal cord"),
+        ] {
+            attributes.insert(sample, tissue);
+        }
+        let samples = input.replace(
+            "Brain_Cortex Liver Brain_Cerebellum Lung Brain_Spinal_cord",
+            "S1 S2 S3 S4 S5",
+        );
+        let summary = loader.load_samples(Cursor::new(samples), &attributes)?;
+        let aggregates = summary.get_sample_aggregates().unwrap();
+        assert_eq!(aggregates.sample_count("Brain"), Some(3));
+        assert_eq!(aggregates.median().get("Gene1", "Brain"), Some(30.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_tissue_include_exclude_lists() -> Result<(), Box<dyn std::error::Error>> {
+        let input = [
+            "#1.2\n1 5\nName Description Liver Lung Cells_Cultured_fibroblasts Lung_Mixed_Cell Testis",
+            "Gene1 Col1a1 10 10 5000 10 10",
+        ]
+        .join("\n");
+
+        // The fibroblasts dominate the cross-tissue mean until they are excluded
+        let summary = GtexSummaryLoader::new(None, Some(1.0))
+            .with_tpm_matrix(true)
+            .with_excluded_tissues(&["Cells_*", "*_Mixed_Cell", "Kidney*"])
+            .load_summary(Cursor::new(input.clone()))?;
+        assert_eq!(
+            summary.metadata.get_tissue_names(),
+            ["Liver", "Lung", "Testis"]
+        );
+        assert!(summary.get_results()["Gene1"].up_regulated.is_empty());
+        assert_eq!(summary.get_tpm("Gene1", "Cells_Cultured_fibroblasts"), None);
+
+        let summary = GtexSummaryLoader::new(None, Some(1.0))
+            .with_included_tissues(&["L*", "Testis"])
+            .with_excluded_tissues(&["Lung_*"])
+            .load_summary(Cursor::new(input.clone()))?;
+        assert_eq!(
+            summary.metadata.get_tissue_names(),
+            ["Liver", "Lung", "Testis"]
+        );
+
+        let error = GtexSummaryLoader::new(None, None)
+            .with_included_tissues(&["Kidney*"])
+            .load_summary(Cursor::new(input.clone()))
+            .unwrap_err();
+        assert!(matches!(error, GtexError::UnknownTissue(pattern) if pattern == "Kidney*"));
+
+        // Sample-level loads drop the samples of the excluded tissues
+        let mut attributes = SampleAttributes::default();
+        for (sample, tissue) in [
+            ("S1", "Liver"),
+            ("S2", "Lung"),
+            ("S3", "Cells - Cultured fibroblasts"),
+            ("S4", "Lung"),
+            ("S5", "Testis"),
+        ] {
+            attributes.insert(sample, tissue);
+        }
+        let samples = input.replace(
+            "Liver Lung Cells_Cultured_fibroblasts Lung_Mixed_Cell Testis",
+            "S1 S2 S3 S4 S5",
+        );
+        let summary = GtexSummaryLoader::new(None, Some(1.0))
+            .with_excluded_tissues(&["Cells_*"])
+            .load_samples(Cursor::new(samples), &attributes)?;
+        assert_eq!(
+            summary.metadata.get_tissue_names(),
+            ["Liver", "Lung", "Testis"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_coexpression() -> Result<(), Box<dyn std::error::Error>> {
+        let input = [
+            "#1.2\n5 4\nName Description Liver Lung Testis Brain",
+            "ENSG1 ALB 1000 10 1 5",
+            "ENSG2 APOA1 900 20 2 4",
+            "ENSG3 SFTPC 1 800 2 3",
+            "ENSG4 FLAT 7 7 7 7",
+            "ENSG5 TTR 200 1 1 2",
+        ]
+        .join("\n");
+        let summary = GtexSummaryLoader::new(None, None)
+            .with_tpm_matrix(true)
+            .load_summary(Cursor::new(input))?;
+        let options = CoexpressionOptions {
+            top_k: 2,
+            ..CoexpressionOptions::default()
+        };
+
+        let neighbours = summary.coexpressed_genes("ALB", &options)?;
+        let ids: Vec<&str> = neighbours.iter().map(|n| n.gene.id.as_str()).collect();
+        assert_eq!(ids, ["ENSG2", "ENSG5"]);
+        assert!(neighbours[0].correlation > 0.9);
+
+        let spearman = CoexpressionOptions {
+            method: CorrelationMethod::Spearman,
+            log_transform: false,
+            ..options
+        };
+        let neighbours = summary.coexpressed_genes("ENSG1", &spearman)?;
+        assert_eq!(neighbours[0].gene.id, "ENSG2");
+        assert_eq!(neighbours[0].correlation, 1.0);
+
+        let error = summary.coexpressed_genes("GAPDH", &options).unwrap_err();
+        assert!(matches!(error, GtexError::UnknownGene(gene) if gene == "GAPDH"));
+
+        // Constant profiles have no edges
+        let mut edge_list = Vec::new();
+        let edges = summary.write_coexpression_edges(
+            &["ALB", "APOA1", "SFTPC", "FLAT"],
+            &CoexpressionOptions::default(),
+            &mut edge_list,
+        )?;
+        let edge_list = String::from_utf8(edge_list)?;
+        let lines: Vec<&str> = edge_list.lines().collect();
+        assert_eq!(edges, lines.len() - 1);
+        assert_eq!(lines[0], "gene_a\tgene_b\tcorrelation");
+        assert!(lines[1].starts_with("ENSG1\tENSG2\t"));
+        assert!(!edge_list.contains("ENSG4"));
+
+        let no_matrix = GtexSummaryLoader::new(None, None).load_summary(Cursor::new(
+            "#1.2\n1 2\nName Description Liver Lung\nENSG1 ALB 1 2",
+        ))?;
+        let error = no_matrix.coexpressed_genes("ALB", &options).unwrap_err();
+        assert!(matches!(error, GtexError::MissingTpmMatrix));
+        Ok(())
+    }
+
+    #[test]
+    fn test_tissue_clustering() -> Result<(), Box<dyn std::error::Error>> {
+        let input = [
+            "#1.2\n4 4\nName Description Brain_Cortex Colon Brain_Cerebellum Stomach",
+            "ENSG1 GFAP 900 2 700 1",
+            "ENSG2 SNAP25 500 1 600 3",
+            "ENSG3 CDX2 1 300 2 100",
+            "ENSG4 MUC2 2 800 1 300",
+        ]
+        .join("\n");
+        let summary = GtexSummaryLoader::new(None, None)
+            .with_tpm_matrix(true)
+            .load_summary(Cursor::new(input))?;
+
+        let correlations = summary.tissue_correlations(None, CorrelationMethod::Pearson, true)?;
+        assert_eq!(
+            correlations.tissue_names(),
+            summary.metadata.get_tissue_names()
+        );
+        let brain = correlations
+            .get_by_name("Brain_Cortex", "Brain_Cerebellum")
+            .unwrap();
+        assert!(brain > 0.9);
+        assert!(correlations.get_by_name("Brain_Cortex", "Colon").unwrap() < 0.0);
+
+        // Brain regions and GI tissues form the two top-level clusters
+        let dendrogram = correlations.to_distances().cluster(Linkage::Average);
+        let newick = dendrogram.to_newick();
+        assert!(newick.starts_with("((Brain_Cortex:"));
+        assert!(newick.contains(",Brain_Cerebellum:"));
+        assert!(newick.contains("(Colon:"));
+
+        let subset = summary.tissue_correlations(
+            Some(&["GFAP", "CDX2"]),
+            CorrelationMethod::Spearman,
+            false,
+        )?;
+        assert_eq!(subset.get_by_name("Colon", "Stomach"), Some(1.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_pca() -> Result<(), Box<dyn std::error::Error>> {
+        let input = [
+            "#1.2\n3 4\nName Description Brain_Cortex Brain_Cerebellum Colon Stomach",
+            "ENSG1 GFAP 1023 511 0 1",
+            "ENSG2 CDX2 0 1 255 127",
+            "ENSG3 ACTB 100 100 100 100",
+        ]
+        .join("\n");
+        let summary = GtexSummaryLoader::new(None, None)
+            .with_tpm_matrix(true)
+            .load_summary(Cursor::new(input))?;
+
+        let pca = summary.pca(2)?;
+        assert_eq!(pca.tissue_names(), summary.metadata.get_tissue_names());
+        assert_eq!(pca.num_components(), 2);
+        assert!(pca.explained_variance_ratio()[0] > 0.9);
+
+        // The first component separates the brain from the GI tract, driven by GFAP and CDX2
+        let brain = pca.tissue_coordinates("Brain_Cortex").unwrap()[0];
+        let colon = pca.tissue_coordinates("Colon").unwrap()[0];
+        assert!(brain * colon < 0.0);
+        let top: Vec<&str> = pca.top_genes(0, 2).iter().map(|(id, _)| *id).collect();
+        assert_eq!(top, ["ENSG1", "ENSG2"]);
+        assert_eq!(pca.gene_loadings("ENSG3").unwrap()[0], 0.0);
+
+        let mut json = Vec::new();
+        pca.write_json(&mut json)?;
+        let saved: Pca = serde_json::from_slice(&json)?;
+        assert_eq!(saved, pca);
+        Ok(())
+    }
+
+    #[test]
+    fn test_highly_variable_genes() -> Result<(), Box<dyn std::error::Error>> {
+        let input = [
+            "#1.2\n4 4\nName Description Liver Lung Testis Brain",
+            "ENSG1 ALB 1000 1 0 2",
+            "ENSG2 ACTB 500 520 480 500",
+            "ENSG3 PRM1 0 0 300 0",
+            "ENSG4 NONE 0 0 0 0",
+        ]
+        .join("\n");
+        let summary = GtexSummaryLoader::new(None, None).load_summary(Cursor::new(input))?;
+
+        let alb = &summary.get_results()["ENSG1"].expression;
+        assert!((alb.mean - 250.75).abs() < 1e-3);
+
+        let selection = summary.highly_variable_genes(VariabilityMethod::CoefficientOfVariation, 2);
+        let ids: Vec<&str> = selection.iter().map(|v| v.gene.id.as_str()).collect();
+        assert_eq!(ids, ["ENSG3", "ENSG1"]);
+        assert!(selection[0].score > selection[1].score);
+
+        let selection = summary.highly_variable_genes(VariabilityMethod::default(), 10);
+        assert_eq!(selection.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_housekeeping_genes() -> Result<(), Box<dyn std::error::Error>> {
+        let input = [
+            "#1.2\n4 4\nName Description Liver Lung Testis Brain",
+            "ENSG1 ALB 1000 1 0 2",
+            "ENSG2 ACTB 500 520 480 500",
+            "ENSG3 GAPDH 300 300 300 300",
+            "ENSG4 RPL13 50 400 100 100",
+        ]
+        .join("\n");
+        let mut summary = GtexSummaryLoader::new(None, None)
+            .with_housekeeping(HousekeepingCriteria::default())
+            .load_summary(Cursor::new(input.clone()))?;
+        let ids: Vec<&str> = summary
+            .get_housekeeping_genes()
+            .iter()
+            .map(|gene| gene.id.as_str())
+            .collect();
+        assert_eq!(ids, ["ENSG3", "ENSG2"]);
+
+        let cached = bincode_round_trip(&summary)?;
+        assert_eq!(
+            cached.get_housekeeping_genes(),
+            summary.get_housekeeping_genes()
+        );
+
+        summary.classify_housekeeping(&HousekeepingCriteria {
+            max_cv: 1.0,
+            max_fold_change: 10.0,
+            ..HousekeepingCriteria::default()
+        });
+        assert_eq!(summary.get_housekeeping_genes().len(), 3);
+
+        let plain = GtexSummaryLoader::new(None, None).load_summary(Cursor::new(input))?;
+        assert!(plain.get_housekeeping_genes().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sample_differential_tests() -> Result<(), Box<dyn std::error::Error>> {
+        let tissues = ["Liver", "Lung", "Brain"];
+        let samples: Vec<String> = (0..15).map(|i| format!("S{}", i)).collect();
+        let mut attributes = SampleAttributes::default();
+        for (i, sample) in samples.iter().enumerate() {
+            attributes.insert(sample, tissues[i / 5]);
+        }
+        let input = [
+            format!("#1.2\n2 15\nName Description {}", samples.join(" ")),
+            "Alb Alb 90 95 100 110 120 1 2 1 3 2 2 1 0 1 2".to_string(),
+            "Actb Actb 50 60 40 70 30 55 45 65 35 50 52 48 58 42 50".to_string(),
+        ];
+        let input = input.join("\n");
+        let loader = |differential_test| {
+            GtexSummaryLoader::new(None, None).with_differential_test(differential_test)
+        };
+
+        let summary = loader(DifferentialTest::default())
+            .load_samples(Cursor::new(input.as_str()), &attributes)?;
+        let alb = &summary.get_results()["Alb"].tissue_tests;
+        assert_eq!(alb.len(), 3);
+        let liver = alb
+            .iter()
+            .find(|test| test.tissue_name(&summary.metadata) == Some("Liver"))
+            .unwrap();
+        assert!(liver.log2_fold_change > 5.0);
+        assert!(liver.p_value < 0.01);
+        assert!(liver.q_value.unwrap() >= liver.p_value);
+        assert_eq!(liver.call, Some(Regulation::Up));
+        assert!(summary.get_results()["Actb"]
+            .tissue_tests
+            .iter()
+            .all(|test| test.call.is_none() && test.q_value.is_some()));
+
+        let pair = DifferentialTest {
+            test: StatisticalTest::WelchT,
+            contrasts: vec![Contrast::Pair {
+                tissue: "Lung".to_string(),
+                reference: "Liver".to_string(),
+            }],
+            ..Default::default()
+        };
+        let summary = loader(pair).load_samples(Cursor::new(input.as_str()), &attributes)?;
+        let lung = &summary.get_results()["Alb"].tissue_tests;
+        assert_eq!(lung.len(), 1);
+        assert_eq!(lung[0].reference, summary.metadata.tissue_index("Liver"));
+        assert_eq!(lung[0].call, Some(Regulation::Down));
+
+        let (json, cached) = json_round_trip(&summary)?;
+        assert!(json.contains("\"reference\": \"Liver\""));
+        assert_eq!(cached.get_results()["Alb"].tissue_tests, *lung);
+
+        let unknown = DifferentialTest {
+            contrasts: vec![Contrast::Pair {
+                tissue: "Lung".to_string(),
+                reference: "Testis".to_string(),
+            }],
+            ..Default::default()
+        };
+        assert!(matches!(
+            loader(unknown).load_samples(Cursor::new(input.as_str()), &attributes),
+            Err(GtexError::UnknownTissue(tissue)) if tissue == "Testis"
+        ));
+
+        // Median files have no samples to test
+        let median = "#1.2\n1 3\nName Description Liver Lung Brain\nAlb Alb 100 2 1";
+        assert!(matches!(
+            loader(DifferentialTest::default()).load_summary(Cursor::new(median)),
+            Err(GtexError::DifferentialTestWithoutSamples)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_tissue_groups() -> Result<(), Box<dyn std::error::Error>> {
+        let input = [
+            "v1.2\n2 5\nID SYMBOL Brain_Cortex Brain_Cerebellum Liver Lung Testis",
+            "Gene1 Gfap 255 127 1 3 0",
+            "Gene2 Actb 50 60 55 45 52",
+        ];
+        let summary = GtexSummaryLoader::new(None, None)
+            .with_tpm_matrix(true)
+            .load_summary(Cursor::new(input.join("\n")))?;
+
+        let comparisons = summary.compare(&["Brain_*"], &[])?;
+        assert_eq!(comparisons.len(), 2);
+        assert_eq!(comparisons[0].gene.id, "Gene1");
+        assert_eq!(comparisons[0].call, Some(Regulation::Up));
+        assert_eq!(comparisons[1].call, None);
+
+        let reversed = summary.compare(&["Liver", "Lung"], &["Brain_Cortex"])?;
+        assert_eq!(reversed[0].call, Some(Regulation::Down));
+        assert!(reversed[0].log2_fold_change < -5.0);
+
+        assert!(matches!(
+            summary.compare(&["Brain_*"], &["Heart"]),
+            Err(GtexError::UnknownTissue(tissue)) if tissue == "Heart"
+        ));
+        assert!(matches!(
+            summary.compare(&["Brain_*"], &["*Cortex"]),
+            Err(GtexError::TissueInBothGroups(tissue)) if tissue == "Brain_Cortex"
+        ));
+        assert!(matches!(
+            summary.compare(&["*"], &[]),
+            Err(GtexError::EmptyTissueGroup)
+        ));
+
+        let without =
+            GtexSummaryLoader::new(None, None).load_summary(Cursor::new(input.join("\n")))?;
+        assert!(matches!(
+            without.compare(&["Liver"], &[]),
+            Err(GtexError::MissingTpmMatrix)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_gct_v1_3() -> Result<(), Box<dyn std::error::Error>> {
+        let input = [
+            "#1.3",
+            "2\t3\t2\t1",
+            "id\tSymbol\tBiotype\tT1\tT2\tT3",
+            "Group\tna\tna\tBrain\tBrain\tLiver",
+            "Gene1\tSymbol1\tprotein_coding\t1.2\t3.4\t5.6",
+            "Gene2\tSymbol2\tlncRNA\t2.2\t4.4\t6.6",
+        ];
+        let summary_loader = GtexSummaryLoader::new(None, Some(1.2));
+        let cursor = Cursor::new(input.join("\n").into_bytes());
+        let summary = summary_loader.load_summary(cursor)?;
+
+        assert_eq!(summary.metadata.get_tissue_names(), ["T1", "T2", "T3"]);
+        assert_eq!(summary.get_results().len(), 2);
+        let gene2 = &summary.get_results()["Gene2"];
+        assert_eq!(gene2.symbol, "Symbol2");
+        assert_eq!(
+            gene2.get_annotation(&summary.metadata, "Biotype"),
+            Some("lncRNA")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_gct_v1_3_with_spaces_in_fields() -> Result<(), Box<dyn std::error::Error>> {
+        let input = [
+            "#1.3",
+            "1\t2\t2\t1",
+            "id\tSymbol\tDescription\tBrain - Cortex\tWhole Blood",
+            "SMTSD\tna\tna\tBrain - Cortex\tWhole Blood",
+            "Gene1\tSymbol1\tProtein coding gene\t1.2\t3.4",
+        ];
+        let summary = GtexSummaryLoader::new(None, None)
+            .load_summary(Cursor::new(input.join("\n").into_bytes()))?;
+
+        assert_eq!(
+            summary.metadata.get_tissue_names(),
+            ["Brain - Cortex", "Whole Blood"]
+        );
+        assert_eq!(
+            summary.metadata.get_column_annotation("SMTSD").unwrap(),
+            ["Brain - Cortex", "Whole Blood"]
+        );
+        assert_eq!(
+            summary.get_results()["Gene1"].get_annotation(&summary.metadata, "Description"),
+            Some("Protein coding gene")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_length_v1_3_reports_line_number() {
+        let input = [
+            "#1.3",
+            "2\t3\t1\t1",
+            "id\tSymbol\tT1\tT2\tT3",
+            "Group\tna\tBrain\tBrain\tLiver",
+            "Gene1\tSymbol1\t1.2\t3.4\t5.6",
+            "Gene2\tSymbol2\t2.2\t4.4",
+        ];
+        let summary_loader = GtexSummaryLoader::new(None, None);
+        let cursor = Cursor::new(input.join("\n").into_bytes());
+        let error = summary_loader.load_summary(cursor).unwrap_err();
+        assert!(error.to_string().contains("row number 6"));
+    }
 }
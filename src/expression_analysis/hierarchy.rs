@@ -0,0 +1,254 @@
+use super::gct_metadata::glob_match;
+use super::scoring::{mean_sd, median};
+use super::{GCTMetadata, GtexError, SampleAttributes, TPMValue};
+use std::io::BufRead;
+
+/// Sub-tissue prefixes of the GTEx tissues, as spelled in the GCT column names, and the tissue
+/// they belong to.
+const GTEX_GROUPS: [(&str, &str); 13] = [
+    ("Adipose*", "Adipose"),
+    ("Artery*", "Artery"),
+    ("Brain*", "Brain"),
+    ("Cervix*", "Cervix"),
+    ("Colon*", "Colon"),
+    ("Esophagus*", "Esophagus"),
+    ("Heart*", "Heart"),
+    ("Kidney*", "Kidney"),
+    ("Liver*", "Liver"),
+    ("Pancreas*", "Pancreas"),
+    ("Skin*", "Skin"),
+    ("Small_Intestine*", "Small_Intestine"),
+    ("Stomach*", "Stomach"),
+];
+
+/// How the values of the sub-tissues of a group are combined into one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CollapseMethod {
+    #[default]
+    Max,
+    Mean,
+    Median,
+}
+
+impl CollapseMethod {
+    fn apply(&self, values: &[TPMValue]) -> TPMValue {
+        match self {
+            CollapseMethod::Max => values
+                .iter()
+                .copied()
+                .fold(TPMValue::NEG_INFINITY, f32::max),
+            CollapseMethod::Mean => mean_sd(values).0,
+            CollapseMethod::Median => median(values),
+        }
+    }
+}
+
+/// Maps sub-tissues (e.g. "Brain_Cortex") to the tissue group they belong to ("Brain").
+///
+/// Rules are tissue names or glob patterns, checked in order; tissues matching no rule are
+/// their own group.
+#[derive(Debug, Clone, Default)]
+pub struct TissueHierarchy {
+    rules: Vec<(String, String)>,
+}
+
+impl TissueHierarchy {
+    /// Returns the built-in GTEx grouping: brain regions, colon, stomach, pancreas, liver,
+    /// skin, and so on, are grouped by organ.
+    pub fn gtex() -> Self {
+        Self {
+            rules: GTEX_GROUPS
+                .iter()
+                .map(|(pattern, group)| (pattern.to_string(), group.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Reads a tab-separated mapping with a tissue name or pattern and its group per line.
+    ///
+    /// Empty lines and lines starting with `#` are skipped; any other line without a tab, or
+    /// with an empty tissue or group, is an error.
+    pub fn from_reader<B: BufRead>(data: B) -> Result<Self, GtexError> {
+        let mut hierarchy = Self::default();
+        for (index, line) in data.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once('\t') {
+                Some((tissue, group)) if !tissue.trim().is_empty() && !group.trim().is_empty() => {
+                    hierarchy.add_rule(tissue.trim(), group.trim());
+                }
+                _ => {
+                    return Err(GtexError::MalformedHierarchyLine {
+                        line: index + 1,
+                        content: line,
+                    })
+                }
+            }
+        }
+        Ok(hierarchy)
+    }
+
+    /// Adds a rule after the existing ones.
+    pub fn add_rule(&mut self, pattern: &str, group: &str) {
+        self.rules.push((pattern.to_string(), group.to_string()));
+    }
+
+    /// Appends the rules of `other`, which only apply to tissues no rule of `self` matches.
+    pub fn extend(&mut self, other: TissueHierarchy) {
+        self.rules.extend(other.rules);
+    }
+
+    /// Returns the group of `tissue`.
+    pub fn group_of<'a>(&'a self, tissue: &'a str) -> &'a str {
+        self.rules
+            .iter()
+            .find(|(pattern, _)| glob_match(pattern, tissue))
+            .map_or(tissue, |(_, group)| group.as_str())
+    }
+
+    /// Returns a copy of `attributes` with each sample assigned to the group of its tissue.
+    pub fn group_samples(&self, attributes: &SampleAttributes) -> SampleAttributes {
+        let mut grouped = SampleAttributes::default();
+        for (sample, tissue) in attributes.iter() {
+            grouped.insert(sample, self.group_of(tissue));
+        }
+        grouped
+    }
+}
+
+/// Collapses the tissue columns of a file into tissue groups.
+#[derive(Debug)]
+pub(crate) struct CollapsedColumns {
+    /// Metadata with one column per group, groups in order of first appearance.
+    metadata: GCTMetadata,
+    /// Positions among the row values of the tissues of each group.
+    columns: Vec<Vec<usize>>,
+    method: CollapseMethod,
+}
+
+impl CollapsedColumns {
+    pub(crate) fn new(
+        metadata: &GCTMetadata,
+        hierarchy: &TissueHierarchy,
+        method: CollapseMethod,
+    ) -> Self {
+        let mut groups: Vec<String> = Vec::new();
+        let mut columns: Vec<Vec<usize>> = Vec::new();
+        for (position, tissue) in metadata.get_tissue_names().iter().enumerate() {
+            let group = hierarchy.group_of(tissue);
+            match groups.iter().position(|name| name == group) {
+                Some(index) => columns[index].push(position),
+                None => {
+                    groups.push(group.to_string());
+                    columns.push(vec![position]);
+                }
+            }
+        }
+        Self {
+            metadata: metadata.with_tissue_columns(groups),
+            columns,
+            method,
+        }
+    }
+
+    /// Returns the metadata of the collapsed values.
+    pub(crate) fn metadata(&self) -> &GCTMetadata {
+        &self.metadata
+    }
+
+    /// Combines the values of the tissues of each group.
+    pub(crate) fn collapse(&self, tpms: &[TPMValue]) -> Box<[TPMValue]> {
+        self.columns
+            .iter()
+            .map(|columns| {
+                let values: Vec<TPMValue> = columns.iter().map(|&column| tpms[column]).collect();
+                self.method.apply(&values)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_gtex_hierarchy() {
+        let hierarchy = TissueHierarchy::gtex();
+        assert_eq!(hierarchy.group_of("Brain_Cerebellum"), "Brain");
+        assert_eq!(
+            hierarchy.group_of("Small_Intestine_Terminal_Ileum"),
+            "Small_Intestine"
+        );
+        assert_eq!(hierarchy.group_of("Colon_Transverse_Mucosa"), "Colon");
+        assert_eq!(hierarchy.group_of("Liver"), "Liver");
+        assert_eq!(
+            hierarchy.group_of("Breast_Mammary_Tissue"),
+            "Breast_Mammary_Tissue"
+        );
+        assert_eq!(
+            hierarchy.group_of("Cells_Cultured_fibroblasts"),
+            "Cells_Cultured_fibroblasts"
+        );
+    }
+
+    #[test]
+    fn test_user_hierarchy_takes_precedence() -> Result<(), GtexError> {
+        let tsv = "# tissue\tgroup\nBrain_Cerebell*\tCerebellum\n\nWhole_Blood\tBlood\n";
+        let mut hierarchy = TissueHierarchy::from_reader(Cursor::new(tsv))?;
+        hierarchy.extend(TissueHierarchy::gtex());
+        assert_eq!(
+            hierarchy.group_of("Brain_Cerebellar_Hemisphere"),
+            "Cerebellum"
+        );
+        assert_eq!(hierarchy.group_of("Brain_Cortex"), "Brain");
+        assert_eq!(hierarchy.group_of("Whole_Blood"), "Blood");
+        Ok(())
+    }
+
+    #[test]
+    fn test_malformed_hierarchy_line() {
+        for (tsv, line) in [
+            ("Brain_Cerebell*\tCerebellum\nWhole_Blood Blood\n", 2),
+            ("# tissue\tgroup\n\nLiver\t \n", 3),
+            ("\tBrain\n", 1),
+        ] {
+            let error = TissueHierarchy::from_reader(Cursor::new(tsv)).unwrap_err();
+            assert!(matches!(error, GtexError::MalformedHierarchyLine { .. }));
+            assert_eq!(error.line(), Some(line));
+        }
+    }
+
+    #[test]
+    fn test_collapse_columns() {
+        let column_names = [
+            "Name",
+            "Description",
+            "Brain_A",
+            "Liver",
+            "Brain_B",
+            "Brain_C",
+        ];
+        let metadata = GCTMetadata::new(
+            "#1.2".to_string(),
+            1,
+            6,
+            4,
+            column_names.iter().map(|name| name.to_string()).collect(),
+        );
+        let hierarchy = TissueHierarchy::gtex();
+        let tpms = [1.0, 5.0, 2.0, 6.0];
+        for (method, brain) in [
+            (CollapseMethod::Max, 6.0),
+            (CollapseMethod::Mean, 3.0),
+            (CollapseMethod::Median, 2.0),
+        ] {
+            let collapsed = CollapsedColumns::new(&metadata, &hierarchy, method);
+            assert_eq!(collapsed.metadata().get_tissue_names(), ["Brain", "Liver"]);
+            assert_eq!(*collapsed.collapse(&tpms), [brain, 5.0]);
+        }
+    }
+}
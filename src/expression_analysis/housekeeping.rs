@@ -0,0 +1,110 @@
+use super::{DGEResult, TPMValue};
+use serde::{Deserialize, Serialize};
+
+/// Limits a gene must meet in every tissue to be a housekeeping candidate.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HousekeepingCriteria {
+    /// Minimum TPM in every tissue.
+    pub min_tpm: TPMValue,
+    /// Maximum coefficient of variation (SD over mean) across tissues.
+    pub max_cv: f32,
+    /// Maximum ratio between the highest and the lowest TPM.
+    pub max_fold_change: f32,
+}
+
+impl Default for HousekeepingCriteria {
+    fn default() -> Self {
+        Self {
+            min_tpm: 1.0,
+            max_cv: 0.5,
+            max_fold_change: 4.0,
+        }
+    }
+}
+
+/// A gene stably expressed in every tissue, a candidate normalization reference.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HousekeepingGene {
+    pub id: String,
+    /// Stability score `1 / (1 + CV)`, 1 for a gene with the same TPM in every tissue.
+    pub score: f32,
+    pub cv: f32,
+    /// Highest over lowest TPM.
+    pub fold_change: f32,
+}
+
+impl HousekeepingCriteria {
+    /// Returns the candidate for `gene`, if it meets the criteria.
+    fn classify(&self, gene: &DGEResult) -> Option<HousekeepingGene> {
+        let stats = &gene.expression;
+        let cv = stats.coefficient_of_variation()?;
+        let fold_change = stats.max / stats.min;
+        let stable =
+            stats.min >= self.min_tpm && cv <= self.max_cv && fold_change <= self.max_fold_change;
+        stable.then(|| HousekeepingGene {
+            id: gene.id.to_string(),
+            score: 1.0 / (1.0 + cv),
+            cv,
+            fold_change,
+        })
+    }
+}
+
+/// Returns the genes meeting `criteria`, the most stable first, then by fold change and ID.
+pub(crate) fn find_housekeeping_genes<'a>(
+    genes: impl Iterator<Item = &'a DGEResult>,
+    criteria: &HousekeepingCriteria,
+) -> Vec<HousekeepingGene> {
+    let mut candidates: Vec<HousekeepingGene> =
+        genes.filter_map(|gene| criteria.classify(gene)).collect();
+    candidates.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.fold_change.total_cmp(&b.fold_change))
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_housekeeping_criteria() {
+        let genes = [
+            DGEResult::with_expression("Actb", &[100.0, 120.0, 80.0, 100.0]),
+            DGEResult::with_expression("Gapdh", &[200.0, 200.0, 200.0, 200.0]),
+            DGEResult::with_expression("Low", &[0.5, 0.6, 0.5, 0.6]),
+            DGEResult::with_expression("Alb", &[1000.0, 2.0, 1.0, 3.0]),
+            DGEResult::with_expression("Fold", &[10.0, 10.0, 10.0, 50.0]),
+        ];
+        let candidates = find_housekeeping_genes(genes.iter(), &HousekeepingCriteria::default());
+        let ids: Vec<&str> = candidates.iter().map(|gene| gene.id.as_str()).collect();
+        assert_eq!(ids, ["Gapdh", "Actb"]);
+        assert_eq!(candidates[0].score, 1.0);
+        assert_eq!(candidates[0].fold_change, 1.0);
+        // SD sqrt(200) over mean 100
+        assert!((candidates[1].cv - 200f32.sqrt() / 100.0).abs() < 1e-6);
+        assert_eq!(candidates[1].fold_change, 1.5);
+
+        // The fold-change limit alone rejects a gene with a moderate CV
+        let lenient = HousekeepingCriteria {
+            max_cv: 1.0,
+            ..HousekeepingCriteria::default()
+        };
+        let ids: Vec<String> = find_housekeeping_genes(genes.iter(), &lenient)
+            .into_iter()
+            .map(|gene| gene.id)
+            .collect();
+        assert!(!ids.contains(&"Fold".to_string()));
+        let no_fold_limit = HousekeepingCriteria {
+            max_fold_change: TPMValue::INFINITY,
+            ..lenient
+        };
+        assert_eq!(
+            find_housekeeping_genes(genes.iter(), &no_fold_limit).len(),
+            3
+        );
+    }
+}
@@ -1,10 +1,47 @@
+mod clustering;
+mod coexpression;
+mod comparison;
 mod dge;
+mod differential;
+mod error;
+mod gates;
 mod gct_metadata;
 mod gtex_summary;
+mod hierarchy;
+mod housekeeping;
 mod models;
+mod pca;
+mod samples;
+mod scoring;
+mod selection;
+mod source;
+mod specificity;
+mod tpm_matrix;
+mod validation;
+mod variable_genes;
 
-pub use dge::DGEResult;
-pub use gct_metadata::GCTMetadata;
+pub use clustering::{Dendrogram, Linkage, MatrixKind, Merge, TissueMatrix};
+pub use coexpression::{CoexpressedGene, CoexpressionEdge, CoexpressionOptions, CorrelationMethod};
+pub use comparison::{ComparisonThresholds, GeneComparison};
+pub use dge::{DGEResult, ExpressionStatus, SuppressedCall};
+pub use differential::{
+    adjust_p_values, Contrast, DifferentialTest, PValueCorrection, Regulation, StatisticalTest,
+    TissueTest,
+};
+pub use error::GtexError;
+pub use gates::{ExpressionGate, ExpressionGates};
+pub use gct_metadata::{ColumnAnnotation, GCTMetadata};
+pub use gtex_summary::DGEResultStream;
 pub use gtex_summary::GtexSummary;
 pub use gtex_summary::GtexSummaryLoader;
-pub use models::{TPMValue, ZScoreValue};
+pub use hierarchy::{CollapseMethod, TissueHierarchy};
+pub use housekeeping::{HousekeepingCriteria, HousekeepingGene};
+pub use models::{PValue, TPMValue, TissueIndex, ZScoreValue};
+pub use pca::Pca;
+pub use samples::{SampleAggregates, SampleAttributes};
+pub use scoring::{ExpressionStats, ScoringMethod};
+pub use source::{decode_expression_source, open_expression_source, Compression};
+pub use specificity::{SpecificityIndices, SpecificityMetric};
+pub use tpm_matrix::TpmMatrix;
+pub use validation::{ErrorPolicy, RejectedRow, RowCountStatus, ValidationReport};
+pub use variable_genes::{VariabilityMethod, VariableGene};
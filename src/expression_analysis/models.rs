@@ -1,2 +1,6 @@
 pub type ZScoreValue = f32;
 pub type TPMValue = f32;
+/// p-values and adjusted p-values, in double precision to keep very small values.
+pub type PValue = f64;
+/// Position of a tissue column in `GCTMetadata::column_names`.
+pub type TissueIndex = u32;
@@ -0,0 +1,317 @@
+use super::TPMValue;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Sweeps of the Jacobi eigenvalue iteration before giving up on convergence.
+const MAX_JACOBI_SWEEPS: usize = 100;
+
+/// Principal component analysis of the tissues, each described by the log2(TPM + 1) of every
+/// gene, as returned by `GtexSummary::pca`.
+///
+/// Components are sorted by decreasing variance. Their sign is arbitrary, and is set so that
+/// the tissue furthest from the origin has a positive coordinate.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pca {
+    tissue_names: Vec<String>,
+    gene_ids: Vec<String>,
+    explained_variance: Vec<f32>,
+    explained_variance_ratio: Vec<f32>,
+    /// `coordinates[tissue * num_components + component]`
+    coordinates: Vec<f32>,
+    /// `loadings[gene * num_components + component]`
+    loadings: Vec<f32>,
+}
+
+impl Pca {
+    /// Computes the first `num_components` components of the tissue columns, each holding
+    /// the (already transformed) value of every gene.
+    ///
+    /// The genes are centred across tissues, and the eigenvectors of the tissue × tissue Gram
+    /// matrix are found by Jacobi rotations, which is exact and cheap for the few dozen
+    /// tissues of GTEx whatever the number of genes. Components without variance are
+    /// left out.
+    pub(crate) fn from_columns(
+        tissue_names: Vec<String>,
+        gene_ids: Vec<String>,
+        columns: &[Vec<f64>],
+        num_components: usize,
+    ) -> Self {
+        let num_tissues = columns.len();
+        let num_genes = gene_ids.len();
+        let means: Vec<f64> = (0..num_genes)
+            .map(|gene| columns.iter().map(|column| column[gene]).sum::<f64>() / num_tissues as f64)
+            .collect();
+        let centred: Vec<Vec<f64>> = columns
+            .iter()
+            .map(|column| {
+                column
+                    .iter()
+                    .zip(&means)
+                    .map(|(x, mean)| x - mean)
+                    .collect()
+            })
+            .collect();
+
+        let mut gram = vec![vec![0.0; num_tissues]; num_tissues];
+        for a in 0..num_tissues {
+            for b in a..num_tissues {
+                let dot: f64 = centred[a].iter().zip(&centred[b]).map(|(x, y)| x * y).sum();
+                gram[a][b] = dot;
+                gram[b][a] = dot;
+            }
+        }
+        let total_variance: f64 = (0..num_tissues).map(|tissue| gram[tissue][tissue]).sum();
+        let (eigenvalues, eigenvectors) = symmetric_eigen(gram);
+
+        let mut order: Vec<usize> = (0..num_tissues).collect();
+        order.sort_by(|&a, &b| eigenvalues[b].total_cmp(&eigenvalues[a]));
+        let tolerance = total_variance * 1e-10;
+        order.retain(|&component| eigenvalues[component] > tolerance);
+        order.truncate(num_components);
+
+        let k = order.len();
+        let degrees_of_freedom = num_tissues.saturating_sub(1).max(1) as f64;
+        let mut pca = Self {
+            tissue_names,
+            gene_ids,
+            explained_variance: Vec::with_capacity(k),
+            explained_variance_ratio: Vec::with_capacity(k),
+            coordinates: vec![0.0; num_tissues * k],
+            loadings: vec![0.0; num_genes * k],
+        };
+        for (component, &eigen) in order.iter().enumerate() {
+            let eigenvalue = eigenvalues[eigen];
+            let mut vector: Vec<f64> = eigenvectors.iter().map(|row| row[eigen]).collect();
+            let furthest = vector
+                .iter()
+                .copied()
+                .max_by(|a, b| a.abs().total_cmp(&b.abs()))
+                .unwrap_or_default();
+            if furthest < 0.0 {
+                vector.iter_mut().for_each(|x| *x = -*x);
+            }
+
+            let singular_value = eigenvalue.sqrt();
+            for (tissue, x) in vector.iter().enumerate() {
+                pca.coordinates[tissue * k + component] = (x * singular_value) as f32;
+            }
+            for gene in 0..num_genes {
+                let loading: f64 = centred
+                    .iter()
+                    .zip(&vector)
+                    .map(|(column, x)| column[gene] * x)
+                    .sum();
+                pca.loadings[gene * k + component] = (loading / singular_value) as f32;
+            }
+            pca.explained_variance
+                .push((eigenvalue / degrees_of_freedom) as f32);
+            pca.explained_variance_ratio
+                .push((eigenvalue / total_variance) as f32);
+        }
+        pca
+    }
+
+    pub fn num_components(&self) -> usize {
+        self.explained_variance.len()
+    }
+
+    /// Returns the tissue names, in the order of the summary metadata.
+    pub fn tissue_names(&self) -> &[String] {
+        &self.tissue_names
+    }
+
+    /// Returns the gene IDs, in the order of the TPM matrix.
+    pub fn gene_ids(&self) -> &[String] {
+        &self.gene_ids
+    }
+
+    /// Variance of the tissue coordinates along each component.
+    pub fn explained_variance(&self) -> &[f32] {
+        &self.explained_variance
+    }
+
+    /// Fraction of the total variance explained by each component.
+    pub fn explained_variance_ratio(&self) -> &[f32] {
+        &self.explained_variance_ratio
+    }
+
+    /// Returns the coordinates of `tissue` on each component.
+    pub fn tissue_coordinates(&self, tissue: &str) -> Option<&[f32]> {
+        let position = self.tissue_names.iter().position(|name| name == tissue)?;
+        let k = self.num_components();
+        Some(&self.coordinates[position * k..(position + 1) * k])
+    }
+
+    /// Returns the loadings of `gene_id`, its weight in each component.
+    pub fn gene_loadings(&self, gene_id: &str) -> Option<&[f32]> {
+        let position = self.gene_ids.iter().position(|id| id == gene_id)?;
+        let k = self.num_components();
+        Some(&self.loadings[position * k..(position + 1) * k])
+    }
+
+    /// Returns the `n` genes with the largest absolute loading on `component`, the genes
+    /// that drive the separation of the tissues along it.
+    pub fn top_genes(&self, component: usize, n: usize) -> Vec<(&str, f32)> {
+        let k = self.num_components();
+        if component >= k {
+            return Vec::new();
+        }
+        let mut genes: Vec<(&str, f32)> = self
+            .gene_ids
+            .iter()
+            .enumerate()
+            .map(|(gene, id)| (id.as_str(), self.loadings[gene * k + component]))
+            .collect();
+        genes.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()).then_with(|| a.0.cmp(b.0)));
+        genes.truncate(n);
+        genes
+    }
+
+    /// Writes the tissue coordinates as a tab-separated table, one row per tissue and one
+    /// `PC<n>` column per component.
+    pub fn write_tissue_coordinates<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        let header: Vec<String> = (1..=self.num_components())
+            .map(|component| format!("PC{}", component))
+            .collect();
+        writeln!(writer, "tissue\t{}", header.join("\t"))?;
+        for tissue in &self.tissue_names {
+            let coordinates: Vec<String> = self
+                .tissue_coordinates(tissue)
+                .unwrap_or_default()
+                .iter()
+                .map(|x| x.to_string())
+                .collect();
+            writeln!(writer, "{}\t{}", tissue, coordinates.join("\t"))?;
+        }
+        writer.flush()
+    }
+
+    /// Saves the analysis in JSON format, next to the `GtexSummary` it was computed from.
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let file = File::create(path)?;
+        self.write_json(BufWriter::new(file))
+    }
+
+    /// Writes the analysis in the `save_json` format to `writer`.
+    pub fn write_json<W: Write>(&self, writer: W) -> std::io::Result<()> {
+        serde_json::to_writer_pretty(writer, self).map_err(std::io::Error::other)
+    }
+}
+
+/// Eigenvalues and eigenvectors (as columns) of a symmetric matrix, by cyclic Jacobi rotations.
+fn symmetric_eigen(mut matrix: Vec<Vec<f64>>) -> (Vec<f64>, Vec<Vec<f64>>) {
+    let n = matrix.len();
+    let mut vectors: Vec<Vec<f64>> = (0..n)
+        .map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect())
+        .collect();
+    let scale: f64 = matrix.iter().flatten().map(|x| x * x).sum();
+
+    for _ in 0..MAX_JACOBI_SWEEPS {
+        let off_diagonal: f64 = (0..n)
+            .flat_map(|p| (0..n).filter(move |&q| q != p).map(move |q| (p, q)))
+            .map(|(p, q)| matrix[p][q].powi(2))
+            .sum();
+        if off_diagonal <= scale * 1e-24 {
+            break;
+        }
+        for p in 0..n {
+            for q in p + 1..n {
+                if matrix[p][q] == 0.0 {
+                    continue;
+                }
+                let theta = (matrix[q][q] - matrix[p][p]) / (2.0 * matrix[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                // A J, then J^T (A J), and V J
+                for row in matrix.iter_mut().chain(vectors.iter_mut()) {
+                    let (x_p, x_q) = (row[p], row[q]);
+                    row[p] = c * x_p - s * x_q;
+                    row[q] = s * x_p + c * x_q;
+                }
+                let (upper, lower) = matrix.split_at_mut(q);
+                for (a_pk, a_qk) in upper[p].iter_mut().zip(lower[0].iter_mut()) {
+                    let (x_p, x_q) = (*a_pk, *a_qk);
+                    *a_pk = c * x_p - s * x_q;
+                    *a_qk = s * x_p + c * x_q;
+                }
+            }
+        }
+    }
+    ((0..n).map(|i| matrix[i][i]).collect(), vectors)
+}
+
+/// log2(TPM + 1) of a tissue column, the values the PCA is run on.
+pub(crate) fn log_column(column: &[TPMValue]) -> Vec<f64> {
+    column
+        .iter()
+        .map(|&tpm| (tpm as f64 + 1.0).log2())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symmetric_eigen() {
+        let (values, vectors) = symmetric_eigen(vec![vec![2.0, 1.0], vec![1.0, 2.0]]);
+        let mut sorted = values.clone();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        assert!((sorted[0] - 1.0).abs() < 1e-12 && (sorted[1] - 3.0).abs() < 1e-12);
+        // Columns are unit eigenvectors: A v = lambda v
+        for (i, value) in values.iter().enumerate() {
+            let v = [vectors[0][i], vectors[1][i]];
+            assert!((2.0 * v[0] + v[1] - value * v[0]).abs() < 1e-12);
+            assert!((v[0].powi(2) + v[1].powi(2) - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_tissues_on_a_line() {
+        // Both genes rise together, so a single component explains everything
+        let pca = Pca::from_columns(
+            vec!["T1".to_string(), "T2".to_string(), "T3".to_string()],
+            vec!["G1".to_string(), "G2".to_string()],
+            &[vec![0.0, 0.0], vec![1.0, 1.0], vec![3.0, 3.0]],
+            2,
+        );
+        assert_eq!(pca.num_components(), 1);
+        assert!((pca.explained_variance_ratio()[0] - 1.0).abs() < 1e-6);
+        // Centred values -4/3, -1/3 and 5/3 on both genes, projected on (1, 1) / sqrt(2)
+        let t3 = pca.tissue_coordinates("T3").unwrap()[0];
+        assert!((t3 - 5.0 / 3.0 * 2f32.sqrt()).abs() < 1e-5);
+        assert!((pca.explained_variance()[0] - 14.0 / 3.0).abs() < 1e-5);
+        let loading = pca.gene_loadings("G1").unwrap()[0];
+        assert!((loading - 0.5f32.sqrt()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_components_sorted_by_variance() {
+        let pca = Pca::from_columns(
+            ["A", "B", "C", "D"].map(String::from).to_vec(),
+            ["G1", "G2", "G3"].map(String::from).to_vec(),
+            &[
+                vec![10.0, 0.0, 1.0],
+                vec![-10.0, 0.0, 1.0],
+                vec![0.0, 2.0, 1.0],
+                vec![0.0, -2.0, 1.0],
+            ],
+            5,
+        );
+        assert_eq!(pca.num_components(), 2);
+        let ratio = pca.explained_variance_ratio();
+        assert!((ratio[0] - 200.0 / 208.0).abs() < 1e-6);
+        assert!((ratio[1] - 8.0 / 208.0).abs() < 1e-6);
+        assert_eq!(pca.top_genes(0, 1)[0].0, "G1");
+        assert_eq!(pca.top_genes(1, 1)[0].0, "G2");
+        assert_eq!(pca.gene_loadings("G3").unwrap(), [0.0, 0.0]);
+
+        let mut table = Vec::new();
+        pca.write_tissue_coordinates(&mut table).unwrap();
+        let table = String::from_utf8(table).unwrap();
+        assert!(table.starts_with("tissue\tPC1\tPC2\nA\t"));
+    }
+}
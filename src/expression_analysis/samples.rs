@@ -0,0 +1,330 @@
+use super::scoring::quantile;
+use super::tpm_matrix::TpmMatrixBuilder;
+use super::{GCTMetadata, GtexError, TPMValue, TpmMatrix};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::BufRead;
+
+/// Tissue of each sample, as listed in a GTEx sample attributes file (`SampleAttributesDS.txt`).
+#[derive(Debug, Clone, Default)]
+pub struct SampleAttributes {
+    tissues: HashMap<String, String>,
+}
+
+impl SampleAttributes {
+    /// Column holding the sample IDs.
+    pub const SAMPLE_COLUMN: &'static str = "SAMPID";
+    /// Column holding the detailed tissue of each sample.
+    pub const TISSUE_COLUMN: &'static str = "SMTSD";
+
+    /// Reads the tab-separated attributes file, mapping `SAMPID` to `SMTSD`.
+    ///
+    /// Samples without a tissue are left out.
+    pub fn from_reader<B: BufRead>(data: B) -> Result<Self, GtexError> {
+        let mut lines = data.lines();
+        let header = lines.next().transpose()?.unwrap_or_default();
+        let column = |name: &str| {
+            header
+                .split('\t')
+                .position(|field| field.trim() == name)
+                .ok_or_else(|| GtexError::MissingAttributeColumn(name.to_string()))
+        };
+        let sample_column = column(Self::SAMPLE_COLUMN)?;
+        let tissue_column = column(Self::TISSUE_COLUMN)?;
+
+        let mut attributes = Self::default();
+        for line in lines {
+            let line = line?;
+            let fields: Vec<&str> = line.split('\t').collect();
+            if let (Some(sample), Some(tissue)) =
+                (fields.get(sample_column), fields.get(tissue_column))
+            {
+                attributes.insert(sample.trim(), tissue.trim());
+            }
+        }
+        Ok(attributes)
+    }
+
+    /// Assigns `sample` to `tissue`; empty IDs or tissues are ignored.
+    pub fn insert(&mut self, sample: &str, tissue: &str) {
+        if !sample.is_empty() && !tissue.is_empty() {
+            self.tissues.insert(sample.to_string(), tissue.to_string());
+        }
+    }
+
+    /// Returns the sample IDs with their tissue.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.tissues
+            .iter()
+            .map(|(sample, tissue)| (sample.as_str(), tissue.as_str()))
+    }
+
+    /// Returns the tissue of `sample`.
+    pub fn tissue(&self, sample: &str) -> Option<&str> {
+        self.tissues.get(sample).map(|tissue| tissue.as_str())
+    }
+
+    /// Keeps only the samples for which `keep(sample, tissue)` is `true`, e.g. to recompute the
+    /// tissue statistics on a subset of donors.
+    pub fn retain(&mut self, mut keep: impl FnMut(&str, &str) -> bool) {
+        self.tissues.retain(|sample, tissue| keep(sample, tissue));
+    }
+
+    pub fn len(&self) -> usize {
+        self.tissues.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tissues.is_empty()
+    }
+
+    /// Returns a copy with the tissues spelled like the columns of the median GCT files (see
+    /// `gct_tissue_name`).
+    pub(crate) fn with_gct_tissue_names(&self) -> Self {
+        let tissues = self
+            .tissues
+            .iter()
+            .map(|(sample, tissue)| (sample.clone(), gct_tissue_name(tissue)))
+            .collect();
+        Self { tissues }
+    }
+}
+
+/// Spells a sample attribute tissue like a GTEx median GCT column: " - " and spaces become `_`
+/// and parentheses are dropped ("Brain - Anterior cingulate cortex (BA24)" becomes
+/// "Brain_Anterior_cingulate_cortex_BA24").
+fn gct_tissue_name(tissue: &str) -> String {
+    tissue
+        .replace(" - ", "_")
+        .replace(['(', ')'], "")
+        .replace(' ', "_")
+}
+
+/// Per-tissue statistics of a sample-level load, computed across the samples of each tissue.
+///
+/// Matrices share the gene and tissue order of the summary they belong to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SampleAggregates {
+    sample_counts: Vec<usize>,
+    median: TpmMatrix,
+    mean: TpmMatrix,
+    iqr: TpmMatrix,
+}
+
+impl SampleAggregates {
+    /// Returns the number of samples of each tissue, in tissue order.
+    pub fn sample_counts(&self) -> &[usize] {
+        &self.sample_counts
+    }
+
+    /// Returns the number of samples of `tissue`.
+    pub fn sample_count(&self, tissue: &str) -> Option<usize> {
+        self.median
+            .tissue_position(tissue)
+            .map(|position| self.sample_counts[position])
+    }
+
+    /// Median TPM of each gene in each tissue, the values the analysis is run on.
+    pub fn median(&self) -> &TpmMatrix {
+        &self.median
+    }
+
+    /// Mean TPM of each gene in each tissue.
+    pub fn mean(&self) -> &TpmMatrix {
+        &self.mean
+    }
+
+    /// Interquartile range of the TPM of each gene in each tissue.
+    pub fn iqr(&self) -> &TpmMatrix {
+        &self.iqr
+    }
+
+    pub(crate) fn build_index(&mut self) {
+        self.median.build_index();
+        self.mean.build_index();
+        self.iqr.build_index();
+    }
+}
+
+/// Statistics of one gene in each tissue, in tissue order.
+#[derive(Debug)]
+pub(crate) struct TissueStats {
+    pub(crate) median: Box<[TPMValue]>,
+    pub(crate) mean: Box<[TPMValue]>,
+    pub(crate) iqr: Box<[TPMValue]>,
+}
+
+/// Groups the sample columns of a sample-level GCT file by tissue.
+#[derive(Debug)]
+pub(crate) struct SampleGrouping {
+    /// Metadata with one column per tissue, tissues sorted by name.
+    metadata: GCTMetadata,
+    /// Positions among the row values of the samples of each tissue.
+    columns: Vec<Vec<usize>>,
+}
+
+impl SampleGrouping {
+    /// Matches the sample columns of `samples` with their tissue in `attributes`.
+    ///
+    /// Columns of samples missing from the attributes are ignored. The per-sample column
+    /// annotations of a GCT 1.3 file have no per-tissue value, so the grouped metadata has
+    /// none.
+    pub(crate) fn new(
+        samples: &GCTMetadata,
+        attributes: &SampleAttributes,
+    ) -> Result<Self, GtexError> {
+        let mut groups: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (position, sample) in samples.get_tissue_names().iter().enumerate() {
+            if let Some(tissue) = attributes.tissue(sample) {
+                groups.entry(tissue).or_default().push(position);
+            }
+        }
+        if groups.is_empty() {
+            return Err(GtexError::NoTissueSamples);
+        }
+
+        let metadata =
+            samples.with_tissue_columns(groups.keys().map(|tissue| tissue.to_string()).collect());
+        Ok(Self {
+            metadata,
+            columns: groups.into_values().collect(),
+        })
+    }
+
+    /// Returns the metadata of the per-tissue values.
+    pub(crate) fn metadata(&self) -> &GCTMetadata {
+        &self.metadata
+    }
+
+    /// Returns the positions among the row values of the samples of each tissue.
+    pub(crate) fn columns(&self) -> &[Vec<usize>] {
+        &self.columns
+    }
+
+    pub(crate) fn sample_counts(&self) -> Vec<usize> {
+        self.columns.iter().map(|columns| columns.len()).collect()
+    }
+
+    /// Computes the per-tissue statistics of a row of sample values.
+    pub(crate) fn aggregate(&self, tpms: &[TPMValue]) -> TissueStats {
+        let num_tissues = self.columns.len();
+        let mut median = Vec::with_capacity(num_tissues);
+        let mut mean = Vec::with_capacity(num_tissues);
+        let mut iqr = Vec::with_capacity(num_tissues);
+        for columns in &self.columns {
+            let mut values: Vec<TPMValue> = columns.iter().map(|&column| tpms[column]).collect();
+            values.sort_by(|a, b| a.total_cmp(b));
+            median.push(quantile(&values, 0.5));
+            mean.push(values.iter().sum::<TPMValue>() / values.len() as TPMValue);
+            iqr.push(quantile(&values, 0.75) - quantile(&values, 0.25));
+        }
+        TissueStats {
+            median: median.into_boxed_slice(),
+            mean: mean.into_boxed_slice(),
+            iqr: iqr.into_boxed_slice(),
+        }
+    }
+}
+
+/// Collects the rows of `SampleAggregates` while a file is being read.
+#[derive(Debug, Default)]
+pub(crate) struct SampleAggregatesBuilder {
+    median: TpmMatrixBuilder,
+    mean: TpmMatrixBuilder,
+    iqr: TpmMatrixBuilder,
+}
+
+impl SampleAggregatesBuilder {
+    pub(crate) fn push(&mut self, gene_id: &str, stats: &TissueStats) {
+        self.median.push(gene_id, &stats.median);
+        self.mean.push(gene_id, &stats.mean);
+        self.iqr.push(gene_id, &stats.iqr);
+    }
+
+    pub(crate) fn build(self, grouping: &SampleGrouping) -> SampleAggregates {
+        let tissue_names = grouping.metadata().get_tissue_names().to_vec();
+        SampleAggregates {
+            sample_counts: grouping.sample_counts(),
+            median: self.median.build(tissue_names.clone()),
+            mean: self.mean.build(tissue_names.clone()),
+            iqr: self.iqr.build(tissue_names),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression_analysis::ColumnAnnotation;
+    use std::io::Cursor;
+
+    const ATTRIBUTES: &str = "SAMPID\tSMTS\tSMTSD\n\
+        S1\tLiver\tLiver\n\
+        S2\tLung\tLung\n\
+        S3\tLiver\tLiver\n\
+        S4\tBrain\t\n\
+        S5\tLiver\tLiver\n";
+
+    #[test]
+    fn test_attributes_from_reader() -> Result<(), GtexError> {
+        let attributes = SampleAttributes::from_reader(Cursor::new(ATTRIBUTES))?;
+        assert_eq!(attributes.len(), 4);
+        assert_eq!(attributes.tissue("S3"), Some("Liver"));
+        assert_eq!(attributes.tissue("S4"), None);
+
+        let error =
+            SampleAttributes::from_reader(Cursor::new("SAMPID\tSMTS\nS1\tLiver")).unwrap_err();
+        assert!(matches!(error, GtexError::MissingAttributeColumn(column) if column == "SMTSD"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_grouping_statistics() -> Result<(), GtexError> {
+        let attributes = SampleAttributes::from_reader(Cursor::new(ATTRIBUTES))?;
+        let column_names = ["Name", "Description", "S1", "S2", "S3", "S4", "S5", "S6"];
+        let mut samples = GCTMetadata::new(
+            "#1.2".to_string(),
+            1,
+            8,
+            6,
+            column_names.iter().map(|name| name.to_string()).collect(),
+        );
+        samples.column_annotations.push(ColumnAnnotation {
+            name: "Donor".to_string(),
+            values: ["na", "na", "D1", "D1", "D2", "D2", "D3", "D3"]
+                .map(String::from)
+                .to_vec(),
+        });
+        let grouping = SampleGrouping::new(&samples, &attributes)?;
+        assert_eq!(grouping.metadata().get_tissue_names(), ["Liver", "Lung"]);
+        assert_eq!(
+            grouping.metadata().column_names[..2],
+            ["Name", "Description"]
+        );
+        assert_eq!(grouping.sample_counts(), [3, 1]);
+        assert!(grouping.metadata().column_annotations.is_empty());
+
+        let stats = grouping.aggregate(&[1.0, 7.0, 5.0, 100.0, 3.0, 100.0]);
+        assert_eq!(*stats.median, [3.0, 7.0]);
+        assert_eq!(*stats.mean, [3.0, 7.0]);
+        assert_eq!(*stats.iqr, [2.0, 0.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_matching_samples() {
+        let samples = GCTMetadata::new(
+            "#1.2".to_string(),
+            1,
+            3,
+            1,
+            vec![
+                "Name".to_string(),
+                "Description".to_string(),
+                "X".to_string(),
+            ],
+        );
+        let error = SampleGrouping::new(&samples, &SampleAttributes::default()).unwrap_err();
+        assert!(matches!(error, GtexError::NoTissueSamples));
+    }
+}
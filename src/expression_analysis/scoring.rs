@@ -0,0 +1,188 @@
+use super::{TPMValue, ZScoreValue};
+use serde::{Deserialize, Serialize};
+
+/// Scale factor making the MAD a consistent estimator of the SD for normal data.
+const MAD_SCALE: TPMValue = 1.4826;
+/// Scales the mean absolute deviation to the SD of a normal distribution (sqrt(pi / 2)).
+const MEAN_AD_SCALE: TPMValue = 1.2533;
+
+/// How the per-tissue scores of a gene are computed from its TPM values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ScoringMethod {
+    /// Classic z-score: population mean and SD across tissues.
+    #[default]
+    ZScore,
+    /// Robust z-score: median and MAD (scaled by 1.4826) across tissues, so that a single
+    /// extreme tissue does not inflate the spread. When half of the tissues or more share one
+    /// value the MAD is 0, and the mean absolute deviation from the median (scaled by 1.2533)
+    /// is used instead.
+    RobustZScore,
+    /// Classic z-score computed on log2(TPM + 1).
+    Log2ZScore,
+}
+
+impl ScoringMethod {
+    /// Returns the score of each tissue, in the order of `tpms`.
+    pub fn scores(&self, tpms: &[TPMValue]) -> Vec<ZScoreValue> {
+        let (values, center, scale) = self.center_and_scale(tpms);
+        values.iter().map(|x| (x - center) / scale).collect()
+    }
+
+    /// Returns the (possibly transformed) values with their center and scale.
+    pub fn center_and_scale(&self, tpms: &[TPMValue]) -> (Vec<TPMValue>, TPMValue, TPMValue) {
+        match self {
+            ScoringMethod::ZScore => {
+                let (mean, sd) = mean_sd(tpms);
+                (tpms.to_vec(), mean, sd)
+            }
+            ScoringMethod::RobustZScore => {
+                let center = median(tpms);
+                let deviations: Vec<TPMValue> = tpms.iter().map(|x| (x - center).abs()).collect();
+                let mad = median(&deviations);
+                let scale = if mad > 0.0 {
+                    MAD_SCALE * mad
+                } else {
+                    MEAN_AD_SCALE * mean_sd(&deviations).0
+                };
+                (tpms.to_vec(), center, scale)
+            }
+            ScoringMethod::Log2ZScore => {
+                let values: Vec<TPMValue> = tpms.iter().map(|x| (x + 1.0).log2()).collect();
+                let (mean, sd) = mean_sd(&values);
+                (values, mean, sd)
+            }
+        }
+    }
+}
+
+/// Summary statistics of the TPM values of a gene across tissues, kept on each `DGEResult`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct ExpressionStats {
+    /// Mean TPM.
+    pub mean: TPMValue,
+    /// Population standard deviation of the TPM.
+    pub sd: TPMValue,
+    /// Lowest TPM.
+    pub min: TPMValue,
+    /// Highest TPM.
+    pub max: TPMValue,
+    /// Mean of log2(TPM + 1).
+    pub log2_mean: TPMValue,
+    /// Population standard deviation of log2(TPM + 1).
+    pub log2_sd: TPMValue,
+}
+
+impl ExpressionStats {
+    pub fn from_tpms(tpms: &[TPMValue]) -> Self {
+        let (mean, sd) = mean_sd(tpms);
+        let log2_values: Vec<TPMValue> = tpms.iter().map(|x| (x + 1.0).log2()).collect();
+        let (log2_mean, log2_sd) = mean_sd(&log2_values);
+        Self {
+            mean,
+            sd,
+            min: tpms.iter().copied().fold(TPMValue::INFINITY, TPMValue::min),
+            max: tpms
+                .iter()
+                .copied()
+                .fold(TPMValue::NEG_INFINITY, TPMValue::max),
+            log2_mean,
+            log2_sd,
+        }
+    }
+
+    /// Standard deviation over mean, `None` for genes that are not expressed.
+    pub fn coefficient_of_variation(&self) -> Option<TPMValue> {
+        (self.mean > 0.0).then(|| self.sd / self.mean)
+    }
+}
+
+/// Population mean and standard deviation.
+pub(crate) fn mean_sd(values: &[TPMValue]) -> (TPMValue, TPMValue) {
+    let mean: TPMValue = values.iter().copied().sum::<TPMValue>() / values.len() as TPMValue;
+    let variance: TPMValue =
+        values.iter().map(|x| (x - mean).powi(2)).sum::<TPMValue>() / values.len() as TPMValue;
+    (mean, variance.sqrt())
+}
+
+/// Median of the values, averaging the two middle ones for even lengths.
+pub(crate) fn median(values: &[TPMValue]) -> TPMValue {
+    if values.is_empty() {
+        return TPMValue::NAN;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let middle = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[middle - 1] + sorted[middle]) / 2.0
+    } else {
+        sorted[middle]
+    }
+}
+
+/// Quantile `p` of sorted values, interpolating linearly between the closest ranks.
+pub(crate) fn quantile(sorted: &[TPMValue], p: TPMValue) -> TPMValue {
+    if sorted.is_empty() {
+        return TPMValue::NAN;
+    }
+    let rank = p * (sorted.len() - 1) as TPMValue;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as TPMValue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&[3.0, 1.0, 2.0]), 2.0);
+        assert_eq!(median(&[4.0, 1.0, 2.0, 3.0]), 2.5);
+    }
+
+    #[test]
+    fn test_quantile() {
+        let sorted = [1.0, 2.0, 3.0, 4.0, 5.0];
+        assert_eq!(quantile(&sorted, 0.5), 3.0);
+        assert_eq!(quantile(&sorted, 0.25), 2.0);
+        assert_eq!(quantile(&[1.0, 2.0], 0.25), 1.25);
+    }
+
+    #[test]
+    fn test_classic_zscore() {
+        let scores = ScoringMethod::ZScore.scores(&[1.0, 2.0, 3.0]);
+        let sd = (2.0f32 / 3.0).sqrt();
+        assert!((scores[0] + 1.0 / sd).abs() < 1e-5);
+        assert_eq!(scores[1], 0.0);
+    }
+
+    #[test]
+    fn test_robust_zscore_ignores_outlier() {
+        let tpms = [1.0, 2.0, 2.0, 3.0, 3.0, 4.0, 1000.0];
+        let robust = ScoringMethod::RobustZScore.scores(&tpms);
+        let classic = ScoringMethod::ZScore.scores(&tpms);
+
+        // median 3, MAD 1: the second largest tissue keeps a clear robust score
+        assert!((robust[5] - 1.0 / MAD_SCALE).abs() < 1e-5);
+        assert!(robust[6] > 100.0);
+        assert!(classic[6] < 3.0);
+    }
+
+    #[test]
+    fn test_robust_zscore_falls_back_to_mean_absolute_deviation() {
+        // 4 of 5 tissues at 0: MAD 0, mean absolute deviation 10
+        let scores = ScoringMethod::RobustZScore.scores(&[0.0, 0.0, 0.0, 0.0, 50.0]);
+        assert_eq!(scores[0], 0.0);
+        assert!((scores[4] - 5.0 / MEAN_AD_SCALE).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_log2_zscore() {
+        let tpms = [0.0, 1.0, 3.0];
+        let scores = ScoringMethod::Log2ZScore.scores(&tpms);
+        let expected = ScoringMethod::ZScore.scores(&[0.0, 1.0, 2.0]);
+        for (score, expected) in scores.iter().zip(expected) {
+            assert!((score - expected).abs() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use super::gct_metadata::glob_match;
+use super::{GCTMetadata, GtexError, SampleAttributes, TPMValue};
+use std::collections::BTreeSet;
+
+/// Tissues to include in and exclude from an analysis, as tissue names or glob patterns.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TissueFilter {
+    /// Tissues to keep; an empty list keeps every tissue.
+    pub(crate) include: Vec<String>,
+    /// Tissues to drop, even if included.
+    pub(crate) exclude: Vec<String>,
+}
+
+impl TissueFilter {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Returns the positions of the kept tissues among `tissues`.
+    ///
+    /// Fails with `GtexError::UnknownTissue` for an include pattern that matches no tissue, and
+    /// with `GtexError::NoTissuesSelected` if no tissue is left. Exclude patterns may match
+    /// nothing, so the same list can be used across GTEx releases.
+    pub(crate) fn select<S: AsRef<str>>(&self, tissues: &[S]) -> Result<Vec<usize>, GtexError> {
+        let matches = |pattern: &String| tissues.iter().any(|t| glob_match(pattern, t.as_ref()));
+        if let Some(pattern) = self.include.iter().find(|pattern| !matches(pattern)) {
+            return Err(GtexError::UnknownTissue(pattern.to_string()));
+        }
+
+        let positions: Vec<usize> = tissues
+            .iter()
+            .enumerate()
+            .filter(|(_, tissue)| self.keeps(tissue.as_ref()))
+            .map(|(position, _)| position)
+            .collect();
+        if positions.is_empty() {
+            return Err(GtexError::NoTissuesSelected);
+        }
+        Ok(positions)
+    }
+
+    fn keeps(&self, tissue: &str) -> bool {
+        let listed =
+            |patterns: &[String]| patterns.iter().any(|pattern| glob_match(pattern, tissue));
+        (self.include.is_empty() || listed(&self.include)) && !listed(&self.exclude)
+    }
+
+    /// Returns a copy of `attributes` without the samples of the dropped tissues.
+    pub(crate) fn filter_samples(
+        &self,
+        attributes: &SampleAttributes,
+    ) -> Result<SampleAttributes, GtexError> {
+        let tissues: Vec<&str> = attributes
+            .iter()
+            .map(|(_, tissue)| tissue)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let kept: BTreeSet<&str> = self
+            .select(&tissues)?
+            .into_iter()
+            .map(|position| tissues[position])
+            .collect();
+
+        let mut filtered = attributes.clone();
+        filtered.retain(|_, tissue| kept.contains(tissue));
+        Ok(filtered)
+    }
+}
+
+/// The tissue columns of a file kept by a `TissueFilter`.
+#[derive(Debug)]
+pub(crate) struct SelectedColumns {
+    /// Metadata with only the kept tissues, in file order.
+    metadata: GCTMetadata,
+    /// Positions among the row values of the kept tissues.
+    positions: Vec<usize>,
+}
+
+impl SelectedColumns {
+    pub(crate) fn new(metadata: &GCTMetadata, filter: &TissueFilter) -> Result<Self, GtexError> {
+        let tissues = metadata.get_tissue_names();
+        let positions = filter.select(tissues)?;
+        let kept = positions
+            .iter()
+            .map(|&position| tissues[position].to_string())
+            .collect();
+        Ok(Self {
+            metadata: metadata.with_tissue_columns(kept),
+            positions,
+        })
+    }
+
+    /// Returns the metadata of the kept tissues.
+    pub(crate) fn metadata(&self) -> &GCTMetadata {
+        &self.metadata
+    }
+
+    /// Returns the values of the kept tissues.
+    pub(crate) fn select(&self, tpms: &[TPMValue]) -> Box<[TPMValue]> {
+        self.positions
+            .iter()
+            .map(|&position| tpms[position])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TISSUES: [&str; 5] = [
+        "Brain_Cortex",
+        "Cells_Cultured_fibroblasts",
+        "Liver",
+        "Lung_Mixed_Cell",
+        "Lung",
+    ];
+
+    fn filter(include: &[&str], exclude: &[&str]) -> TissueFilter {
+        TissueFilter {
+            include: include.iter().map(|s| s.to_string()).collect(),
+            exclude: exclude.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_select_tissues() -> Result<(), GtexError> {
+        assert_eq!(filter(&[], &[]).select(&TISSUES)?, [0, 1, 2, 3, 4]);
+        assert_eq!(
+            filter(&[], &["Cells_*", "*_Mixed_Cell", "Kidney*"]).select(&TISSUES)?,
+            [0, 2, 4]
+        );
+        assert_eq!(filter(&["L*"], &["Lung"]).select(&TISSUES)?, [2, 3]);
+
+        let error = filter(&["Liver", "Kidney*"], &[])
+            .select(&TISSUES)
+            .unwrap_err();
+        assert!(matches!(error, GtexError::UnknownTissue(pattern) if pattern == "Kidney*"));
+        let error = filter(&["Liver"], &["Liv*"]).select(&TISSUES).unwrap_err();
+        assert!(matches!(error, GtexError::NoTissuesSelected));
+        Ok(())
+    }
+
+    #[test]
+    fn test_selected_columns() -> Result<(), GtexError> {
+        let mut column_names = vec!["Name".to_string(), "Description".to_string()];
+        column_names.extend(TISSUES.iter().map(|tissue| tissue.to_string()));
+        let metadata = GCTMetadata::new("#1.2".to_string(), 1, 7, 5, column_names);
+
+        let selected = SelectedColumns::new(&metadata, &filter(&[], &["Cells_*", "*_Mixed_Cell"]))?;
+        assert_eq!(
+            selected.metadata().get_tissue_names(),
+            ["Brain_Cortex", "Liver", "Lung"]
+        );
+        assert_eq!(
+            *selected.select(&[1.0, 2.0, 3.0, 4.0, 5.0]),
+            [1.0, 3.0, 5.0]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+use flate2::read::MultiGzDecoder;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use std::path::Path;
+use xz2::read::XzDecoder;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const XZ_MAGIC: &[u8] = &[0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00];
+
+/// Compression formats recognised by `open_expression_source`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// gzip, including multi-member bgzip files.
+    Gzip,
+    Zstd,
+    Xz,
+    /// Plain, uncompressed text.
+    None,
+}
+
+impl Compression {
+    /// Detects the compression format from the leading bytes of a file.
+    pub fn from_magic(bytes: &[u8]) -> Self {
+        if bytes.starts_with(GZIP_MAGIC) {
+            Compression::Gzip
+        } else if bytes.starts_with(ZSTD_MAGIC) {
+            Compression::Zstd
+        } else if bytes.starts_with(XZ_MAGIC) {
+            Compression::Xz
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// Opens an expression file and returns a buffered reader over its decoded text.
+///
+/// The compression is sniffed from the magic bytes rather than the file extension, so
+/// renamed or extension-less downloads are decoded the same way.
+pub fn open_expression_source<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn BufRead>> {
+    let file = File::open(path)?;
+    decode_expression_source(file)
+}
+
+/// Wraps any reader in the decoder matching its magic bytes.
+pub fn decode_expression_source<R: Read + 'static>(mut reader: R) -> io::Result<Box<dyn BufRead>> {
+    // Read the magic bytes, then chain them back in front of the rest of the stream
+    let mut magic = [0u8; XZ_MAGIC.len()];
+    let mut magic_len = 0;
+    while magic_len < magic.len() {
+        match reader.read(&mut magic[magic_len..]) {
+            Ok(0) => break,
+            Ok(n) => magic_len += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    let compression = Compression::from_magic(&magic[..magic_len]);
+    let reader = BufReader::new(Cursor::new(magic[..magic_len].to_vec()).chain(reader));
+
+    Ok(match compression {
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+        Compression::Xz => Box::new(BufReader::new(XzDecoder::new_multi_decoder(reader))),
+        Compression::None => Box::new(reader),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+    use xz2::write::XzEncoder;
+
+    const CONTENT: &str = "#1.2\n1 2\nName Description T1 T2\nGene1 Symbol1 1.0 2.0\n";
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn decode_to_string(bytes: Vec<u8>) -> String {
+        let mut decoded = String::new();
+        decode_expression_source(Cursor::new(bytes))
+            .unwrap()
+            .read_to_string(&mut decoded)
+            .unwrap();
+        decoded
+    }
+
+    #[test]
+    fn test_detect_compression() {
+        assert_eq!(Compression::from_magic(&gzip(b"x")), Compression::Gzip);
+        assert_eq!(Compression::from_magic(b"#1.2\n"), Compression::None);
+        assert_eq!(Compression::from_magic(b""), Compression::None);
+    }
+
+    #[test]
+    fn test_decode_plain_text() {
+        assert_eq!(decode_to_string(CONTENT.as_bytes().to_vec()), CONTENT);
+    }
+
+    #[test]
+    fn test_decode_multi_member_gzip() {
+        let (head, tail) = CONTENT.split_at(20);
+        let mut bgzip = gzip(head.as_bytes());
+        bgzip.extend(gzip(tail.as_bytes()));
+        assert_eq!(decode_to_string(bgzip), CONTENT);
+    }
+
+    #[test]
+    fn test_decode_zstd() {
+        let compressed = zstd::encode_all(CONTENT.as_bytes(), 0).unwrap();
+        assert_eq!(Compression::from_magic(&compressed), Compression::Zstd);
+        assert_eq!(decode_to_string(compressed), CONTENT);
+    }
+
+    #[test]
+    fn test_decode_xz() {
+        let mut encoder = XzEncoder::new(Vec::new(), 6);
+        encoder.write_all(CONTENT.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(Compression::from_magic(&compressed), Compression::Xz);
+        assert_eq!(decode_to_string(compressed), CONTENT);
+    }
+}
@@ -0,0 +1,159 @@
+use super::TPMValue;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// Standard tissue-specificity indices of a gene.
+///
+/// All indices are computed on log2(TPM + 1), as recommended by Kryuchkova-Mostacci and
+/// Robinson-Rechavi (2017). Per-tissue values follow the order of
+/// `GCTMetadata::get_tissue_names`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpecificityIndices {
+    /// Tau index: 0 for uniform expression, 1 for expression in a single tissue.
+    pub tau: f32,
+    /// Gini coefficient, normalised to [0, 1].
+    pub gini: f32,
+    /// Tissue specificity index: the fraction of the total expression in the top tissue.
+    pub tsi: f32,
+    /// Shannon entropy (bits) of the expression distribution; low values are specific.
+    pub entropy: f32,
+    /// Entropy-based Q statistic per tissue, `entropy - log2(p_t)`; low values are specific.
+    /// `None` for tissues without expression, where Q is infinite.
+    pub q: Vec<Option<f32>>,
+    /// Specificity measure (SPM) per tissue, in [0, 1]; high values are specific.
+    pub spm: Vec<f32>,
+}
+
+/// A gene-level index used to rank genes by tissue specificity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecificityMetric {
+    Tau,
+    Gini,
+    Tsi,
+    Entropy,
+}
+
+impl SpecificityIndices {
+    /// Computes the indices from the TPM values of a gene across tissues.
+    ///
+    /// Returns `None` for fewer than two tissues or a gene that is not expressed anywhere.
+    pub fn from_tpms(tpms: &[TPMValue]) -> Option<Self> {
+        let n = tpms.len();
+        let values: Vec<f32> = tpms.iter().map(|x| (x.max(0.0) + 1.0).log2()).collect();
+        let total: f32 = values.iter().sum();
+        if n < 2 || total <= 0.0 {
+            return None;
+        }
+        let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+
+        let tau = values.iter().map(|x| 1.0 - x / max).sum::<f32>() / (n - 1) as f32;
+
+        let mut sorted = values.clone();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let weighted: f32 = sorted
+            .iter()
+            .enumerate()
+            .map(|(i, x)| (2.0 * (i + 1) as f32 - n as f32 - 1.0) * x)
+            .sum();
+        let gini = weighted / (n as f32 * total) * n as f32 / (n - 1) as f32;
+
+        let tsi = max / total;
+
+        let proportions: Vec<f32> = values.iter().map(|x| x / total).collect();
+        let entropy = -proportions
+            .iter()
+            .filter(|&&p| p > 0.0)
+            .map(|p| p * p.log2())
+            .sum::<f32>();
+        let q = proportions
+            .iter()
+            .map(|&p| (p > 0.0).then(|| entropy - p.log2()))
+            .collect();
+
+        let norm = values.iter().map(|x| x * x).sum::<f32>().sqrt();
+        let spm = values.iter().map(|x| x / norm).collect();
+
+        Some(Self {
+            tau,
+            gini,
+            tsi,
+            entropy,
+            q,
+            spm,
+        })
+    }
+
+    /// Returns the value of a gene-level index.
+    pub fn get(&self, metric: SpecificityMetric) -> f32 {
+        match metric {
+            SpecificityMetric::Tau => self.tau,
+            SpecificityMetric::Gini => self.gini,
+            SpecificityMetric::Tsi => self.tsi,
+            SpecificityMetric::Entropy => self.entropy,
+        }
+    }
+
+    /// Orders two genes so that the more tissue-specific one comes first.
+    ///
+    /// Tau, Gini and TSI grow with specificity, while entropy decreases.
+    pub fn cmp_specificity(&self, other: &Self, metric: SpecificityMetric) -> Ordering {
+        let ordering = self.get(metric).total_cmp(&other.get(metric));
+        match metric {
+            SpecificityMetric::Entropy => ordering,
+            _ => ordering.reverse(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_uniform_expression() {
+        let indices = SpecificityIndices::from_tpms(&[3.0, 3.0, 3.0, 3.0]).unwrap();
+        assert_close(indices.tau, 0.0);
+        assert_close(indices.gini, 0.0);
+        assert_close(indices.tsi, 0.25);
+        assert_close(indices.entropy, 2.0);
+        assert_close(indices.spm[0], 0.5);
+        assert_close(indices.q[0].unwrap(), 4.0);
+    }
+
+    #[test]
+    fn test_single_tissue_expression() {
+        let indices = SpecificityIndices::from_tpms(&[0.0, 0.0, 15.0, 0.0]).unwrap();
+        assert_close(indices.tau, 1.0);
+        assert_close(indices.gini, 1.0);
+        assert_close(indices.tsi, 1.0);
+        assert_close(indices.entropy, 0.0);
+        assert_close(indices.spm[2], 1.0);
+        assert_close(indices.spm[0], 0.0);
+        assert_close(indices.q[2].unwrap(), 0.0);
+        assert_eq!(indices.q[0], None);
+    }
+
+    #[test]
+    fn test_not_expressed_has_no_indices() {
+        assert!(SpecificityIndices::from_tpms(&[0.0, 0.0]).is_none());
+        assert!(SpecificityIndices::from_tpms(&[5.0]).is_none());
+    }
+
+    #[test]
+    fn test_cmp_specificity() {
+        let specific = SpecificityIndices::from_tpms(&[0.0, 0.0, 15.0]).unwrap();
+        let broad = SpecificityIndices::from_tpms(&[5.0, 4.0, 6.0]).unwrap();
+        for metric in [
+            SpecificityMetric::Tau,
+            SpecificityMetric::Gini,
+            SpecificityMetric::Tsi,
+            SpecificityMetric::Entropy,
+        ] {
+            assert_eq!(specific.cmp_specificity(&broad, metric), Ordering::Less);
+        }
+    }
+}
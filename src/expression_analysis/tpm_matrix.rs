@@ -0,0 +1,170 @@
+use super::TPMValue;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The TPM values of the loaded genes, stored column-major (one contiguous column per tissue).
+///
+/// Genes keep the order in which they were read from the file, tissues the order of
+/// `tissue_names`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TpmMatrix {
+    gene_ids: Vec<String>,
+    tissue_names: Vec<String>,
+    /// `values[tissue * gene_ids.len() + gene]`
+    values: Vec<TPMValue>,
+    #[serde(skip)]
+    gene_index: HashMap<String, usize>,
+}
+
+impl TpmMatrix {
+    /// Builds the matrix from row-major values, one row of `tissue_names.len()` values per gene.
+    pub(crate) fn from_rows(
+        gene_ids: Vec<String>,
+        tissue_names: Vec<String>,
+        rows: &[TPMValue],
+    ) -> Self {
+        let num_genes = gene_ids.len();
+        let num_tissues = tissue_names.len();
+        assert_eq!(rows.len(), num_genes * num_tissues);
+
+        let mut values = vec![0.0; rows.len()];
+        for (gene, row) in rows.chunks(num_tissues.max(1)).enumerate() {
+            for (tissue, &tpm) in row.iter().enumerate() {
+                values[tissue * num_genes + gene] = tpm;
+            }
+        }
+
+        let mut matrix = Self {
+            gene_ids,
+            tissue_names,
+            values,
+            gene_index: HashMap::new(),
+        };
+        matrix.build_index();
+        matrix
+    }
+
+    /// Rebuilds the gene ID index, which is not cached.
+    pub(crate) fn build_index(&mut self) {
+        self.gene_index = self
+            .gene_ids
+            .iter()
+            .enumerate()
+            .map(|(index, id)| (id.to_string(), index))
+            .collect();
+    }
+
+    pub fn num_genes(&self) -> usize {
+        self.gene_ids.len()
+    }
+
+    pub fn num_tissues(&self) -> usize {
+        self.tissue_names.len()
+    }
+
+    /// Returns the gene IDs, in row order.
+    pub fn gene_ids(&self) -> &[String] {
+        &self.gene_ids
+    }
+
+    /// Returns the tissue names, in column order.
+    pub fn tissue_names(&self) -> &[String] {
+        &self.tissue_names
+    }
+
+    /// Returns the row of `gene_id`, if the gene is in the matrix.
+    pub fn gene_position(&self, gene_id: &str) -> Option<usize> {
+        self.gene_index.get(gene_id).copied()
+    }
+
+    /// Returns the column of `tissue`, if the tissue is in the matrix.
+    pub fn tissue_position(&self, tissue: &str) -> Option<usize> {
+        self.tissue_names.iter().position(|name| name == tissue)
+    }
+
+    /// Returns the TPM of `gene_id` in `tissue`.
+    pub fn get(&self, gene_id: &str, tissue: &str) -> Option<TPMValue> {
+        let gene = self.gene_position(gene_id)?;
+        let tissue = self.tissue_position(tissue)?;
+        Some(self.values[tissue * self.num_genes() + gene])
+    }
+
+    /// Returns the TPM values of every gene in `tissue`, in row order.
+    pub fn tissue_values(&self, tissue: &str) -> Option<&[TPMValue]> {
+        self.tissue_position(tissue)
+            .map(|tissue| self.column(tissue))
+    }
+
+    /// Returns the TPM values of `gene_id` across tissues, in column order.
+    pub fn gene_values(&self, gene_id: &str) -> Option<Vec<TPMValue>> {
+        self.gene_position(gene_id).map(|gene| self.row(gene))
+    }
+
+    /// Returns the column at position `tissue`.
+    pub fn column(&self, tissue: usize) -> &[TPMValue] {
+        let num_genes = self.num_genes();
+        &self.values[tissue * num_genes..(tissue + 1) * num_genes]
+    }
+
+    /// Returns the row at position `gene`.
+    pub fn row(&self, gene: usize) -> Vec<TPMValue> {
+        (0..self.num_tissues())
+            .map(|tissue| self.values[tissue * self.num_genes() + gene])
+            .collect()
+    }
+}
+
+/// Collects the rows of a `TpmMatrix` while a file is being read.
+#[derive(Debug, Default)]
+pub(crate) struct TpmMatrixBuilder {
+    gene_ids: Vec<String>,
+    rows: Vec<TPMValue>,
+}
+
+impl TpmMatrixBuilder {
+    pub(crate) fn push(&mut self, gene_id: &str, tpms: &[TPMValue]) {
+        self.gene_ids.push(gene_id.to_string());
+        self.rows.extend_from_slice(tpms);
+    }
+
+    pub(crate) fn build(self, tissue_names: Vec<String>) -> TpmMatrix {
+        TpmMatrix::from_rows(self.gene_ids, tissue_names, &self.rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matrix() -> TpmMatrix {
+        TpmMatrix::from_rows(
+            vec!["Gene1".to_string(), "Gene2".to_string()],
+            vec![
+                "Liver".to_string(),
+                "Lung".to_string(),
+                "Testis".to_string(),
+            ],
+            &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0],
+        )
+    }
+
+    #[test]
+    fn test_column_major_accessors() {
+        let matrix = matrix();
+        assert_eq!(matrix.get("Gene2", "Lung"), Some(5.0));
+        assert_eq!(matrix.get("Gene3", "Lung"), None);
+        assert_eq!(matrix.get("Gene1", "Brain"), None);
+        assert_eq!(matrix.tissue_values("Testis").unwrap(), [3.0, 6.0]);
+        assert_eq!(matrix.gene_values("Gene1").unwrap(), [1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn test_builder() {
+        let mut builder = TpmMatrixBuilder::default();
+        builder.push("Gene1", &[1.0, 2.0]);
+        builder.push("Gene2", &[3.0, 4.0]);
+        let matrix = builder.build(vec!["T1".to_string(), "T2".to_string()]);
+        assert_eq!(matrix.column(1), [2.0, 4.0]);
+        assert_eq!(matrix.gene_position("Gene2"), Some(1));
+    }
+}
@@ -0,0 +1,89 @@
+use super::GtexError;
+use serde::{Deserialize, Serialize};
+
+/// How `GtexSummaryLoader` reacts to a malformed row.
+///
+/// Only row-level errors (invalid TPM values, wrong row lengths and duplicate IDs) are
+/// covered by the policy; metadata and I/O errors always abort the load.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// Abort on the first malformed row.
+    #[default]
+    Strict,
+    /// Skip every malformed row and record it in the `ValidationReport`.
+    SkipAndRecord,
+    /// Skip and record malformed rows, but abort once more than `n` rows have been rejected.
+    StopAfter(usize),
+}
+
+impl ErrorPolicy {
+    /// Returns `true` if a row error can be skipped when `rejected` rows were already dropped.
+    pub fn allows_skip(&self, rejected: usize) -> bool {
+        match self {
+            ErrorPolicy::Strict => false,
+            ErrorPolicy::SkipAndRecord => true,
+            ErrorPolicy::StopAfter(max_errors) => rejected < *max_errors,
+        }
+    }
+}
+
+/// A row dropped while loading in a non-strict `ErrorPolicy`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RejectedRow {
+    /// 1-based line number in the file.
+    pub line: usize,
+    pub gene_id: String,
+    pub reason: String,
+}
+
+/// Lists the rows rejected while loading a `GtexSummary`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ValidationReport {
+    pub rejected: Vec<RejectedRow>,
+}
+
+impl ValidationReport {
+    /// Returns `true` if no row was rejected.
+    pub fn is_empty(&self) -> bool {
+        self.rejected.is_empty()
+    }
+
+    /// Records a row-level error; returns `false` if `error` does not refer to a single row.
+    pub fn record(&mut self, error: &GtexError) -> bool {
+        match (error.line(), error.gene_id()) {
+            (Some(line), Some(gene_id)) => {
+                self.rejected.push(RejectedRow {
+                    line,
+                    gene_id: gene_id.to_string(),
+                    reason: error.to_string(),
+                });
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Compares the rows read with the row count declared in the GCT dimension line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RowCountStatus {
+    /// The file holds exactly the declared number of rows.
+    Complete,
+    /// The file ended before the declared number of rows, e.g. a truncated download.
+    Truncated { declared: usize, found: usize },
+    /// The file holds more rows than declared.
+    Overrun { declared: usize, found: usize },
+    /// Loading stopped at `n_max` rows before the end of the file.
+    Partial { rows: usize },
+}
+
+impl RowCountStatus {
+    /// Compares the `found` rows of a fully read file with the `declared` ones.
+    pub fn compare(declared: usize, found: usize) -> Self {
+        match found.cmp(&declared) {
+            std::cmp::Ordering::Equal => RowCountStatus::Complete,
+            std::cmp::Ordering::Less => RowCountStatus::Truncated { declared, found },
+            std::cmp::Ordering::Greater => RowCountStatus::Overrun { declared, found },
+        }
+    }
+}
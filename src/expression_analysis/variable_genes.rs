@@ -0,0 +1,175 @@
+use super::scoring::mean_sd;
+use super::{DGEResult, TPMValue};
+use serde::{Deserialize, Serialize};
+
+/// How genes are ranked by their variability across tissues.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VariabilityMethod {
+    /// Standard deviation over mean of the TPM.
+    CoefficientOfVariation,
+    /// Log dispersion (variance over mean of the TPM), standardised among the genes of
+    /// similar mean expression: genes are binned in `num_bins` equal-width bins of
+    /// log2(mean + 1), and the score is the z-score of the gene within its bin.
+    ///
+    /// Corrects for the dispersion growing with expression; bins with a single gene or no
+    /// spread give a score of 0.
+    BinnedDispersion { num_bins: usize },
+    /// Variance of log2(TPM + 1).
+    LogVariance,
+}
+
+impl Default for VariabilityMethod {
+    fn default() -> Self {
+        VariabilityMethod::BinnedDispersion { num_bins: 20 }
+    }
+}
+
+/// A gene of a highly-variable selection; its mean and SD are in `gene.expression`.
+#[derive(Debug, Clone)]
+pub struct VariableGene<'a> {
+    pub gene: &'a DGEResult,
+    /// Variability of the gene under the selection's method, higher is more variable.
+    pub score: f32,
+}
+
+/// Scores the expressed genes with some spread and returns the `top_n` most variable,
+/// highest score first and ties by ID.
+pub(crate) fn select_variable_genes<'a>(
+    genes: impl Iterator<Item = &'a DGEResult>,
+    method: VariabilityMethod,
+    top_n: usize,
+) -> Vec<VariableGene<'a>> {
+    let genes: Vec<&DGEResult> = genes
+        .filter(|gene| gene.expression.mean > 0.0 && gene.expression.sd > 0.0)
+        .collect();
+    let scores = match method {
+        VariabilityMethod::CoefficientOfVariation => genes
+            .iter()
+            .map(|gene| gene.expression.sd / gene.expression.mean)
+            .collect(),
+        VariabilityMethod::LogVariance => genes
+            .iter()
+            .map(|gene| gene.expression.log2_sd.powi(2))
+            .collect(),
+        VariabilityMethod::BinnedDispersion { num_bins } => binned_dispersions(&genes, num_bins),
+    };
+
+    let mut selection: Vec<VariableGene> = genes
+        .into_iter()
+        .zip(scores)
+        .map(|(gene, score)| VariableGene { gene, score })
+        .collect();
+    selection.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.gene.id.cmp(&b.gene.id))
+    });
+    selection.truncate(top_n);
+    selection
+}
+
+// Z-score of the log dispersion of each gene among the genes of its mean-expression bin
+fn binned_dispersions(genes: &[&DGEResult], num_bins: usize) -> Vec<f32> {
+    let num_bins = num_bins.max(1);
+    let log_means: Vec<TPMValue> = genes
+        .iter()
+        .map(|gene| (gene.expression.mean + 1.0).log2())
+        .collect();
+    let dispersions: Vec<f32> = genes
+        .iter()
+        .map(|gene| (gene.expression.sd.powi(2) / gene.expression.mean).ln())
+        .collect();
+
+    let min = log_means.iter().copied().fold(TPMValue::INFINITY, f32::min);
+    let max = log_means
+        .iter()
+        .copied()
+        .fold(TPMValue::NEG_INFINITY, f32::max);
+    let width = (max - min) / num_bins as TPMValue;
+    let bins: Vec<usize> = log_means
+        .iter()
+        .map(|&x| {
+            if width > 0.0 {
+                (((x - min) / width) as usize).min(num_bins - 1)
+            } else {
+                0
+            }
+        })
+        .collect();
+
+    let mut members: Vec<Vec<f32>> = vec![Vec::new(); num_bins];
+    for (&bin, &dispersion) in bins.iter().zip(&dispersions) {
+        members[bin].push(dispersion);
+    }
+    let moments: Vec<(f32, f32)> = members
+        .iter()
+        .map(|values| match values.len() {
+            0 | 1 => (0.0, 0.0),
+            _ => mean_sd(values),
+        })
+        .collect();
+
+    bins.iter()
+        .zip(&dispersions)
+        .map(|(&bin, dispersion)| match moments[bin] {
+            (mean, sd) if sd > 0.0 => (dispersion - mean) / sd,
+            _ => 0.0,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(selection: &[VariableGene]) -> Vec<String> {
+        selection.iter().map(|v| v.gene.id.clone()).collect()
+    }
+
+    #[test]
+    fn test_coefficient_of_variation_and_log_variance() {
+        let genes = [
+            DGEResult::with_expression("Flat", &[5.0, 5.0, 5.0, 5.0]),
+            DGEResult::with_expression("Off", &[0.0, 0.0, 0.0, 0.0]),
+            DGEResult::with_expression("High", &[1000.0, 1100.0, 900.0, 1000.0]),
+            DGEResult::with_expression("Specific", &[0.0, 0.0, 0.0, 40.0]),
+        ];
+        let selection =
+            select_variable_genes(genes.iter(), VariabilityMethod::CoefficientOfVariation, 5);
+        assert_eq!(ids(&selection), ["Specific", "High"]);
+        // SD sqrt(300) over mean 10
+        assert!((selection[0].score - 300f32.sqrt() / 10.0).abs() < 1e-5);
+
+        let selection = select_variable_genes(genes.iter(), VariabilityMethod::LogVariance, 1);
+        assert_eq!(ids(&selection), ["Specific"]);
+    }
+
+    #[test]
+    fn test_binned_dispersion() {
+        // Two expression bins; within each, one gene is far more dispersed than the others
+        let genes = [
+            DGEResult::with_expression("Low1", &[1.0, 2.0, 1.0, 2.0]),
+            DGEResult::with_expression("Low2", &[1.0, 2.0, 2.0, 1.0]),
+            DGEResult::with_expression("Low3", &[0.0, 3.0, 0.0, 3.0]),
+            DGEResult::with_expression("High1", &[1000.0, 1010.0, 1000.0, 1010.0]),
+            DGEResult::with_expression("High2", &[1000.0, 1020.0, 1000.0, 1020.0]),
+            DGEResult::with_expression("High3", &[500.0, 1500.0, 500.0, 1500.0]),
+        ];
+        let selection = select_variable_genes(
+            genes.iter(),
+            VariabilityMethod::BinnedDispersion { num_bins: 2 },
+            2,
+        );
+        let mut top = ids(&selection);
+        top.sort();
+        assert_eq!(top, ["High3", "Low3"]);
+
+        // A single bin only ranks by dispersion
+        let selection = select_variable_genes(
+            genes.iter(),
+            VariabilityMethod::BinnedDispersion { num_bins: 1 },
+            1,
+        );
+        assert_eq!(ids(&selection), ["High3"]);
+    }
+}
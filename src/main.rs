@@ -1,29 +1,9 @@
-use flate2::read::GzDecoder; //  decompression of gz
+use gtex_analyzer::expression_analysis::open_expression_source;
 use gtex_analyzer::expression_analysis::GCTMetadata;
 use gtex_analyzer::expression_analysis::GtexSummaryLoader;
 use gtex_analyzer::expression_analysis::TPMValue;
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{self, BufRead, BufReader, Read};
-use std::path::Path;
-
-fn decode_file(file_path: &str) -> io::Result<Box<dyn BufRead>> {
-    let path = Path::new(file_path);
-    let file = File::open(path)?;
-
-    // Check if the file is a `.gz` and decode it if necessary
-    if file_path.ends_with(".gz") {
-        let decoder = GzDecoder::new(file);
-        Ok(Box::new(BufReader::new(decoder)))
-    } else {
-        Ok(Box::new(BufReader::new(file)))
-    }
-}
-
-fn read_gct_file<R: Read>(decoder: R) -> io::Result<BufReader<R>> {
-    let reader = io::BufReader::new(decoder);
-    Ok(reader)
-}
+use std::io::{self, BufRead};
 
 pub fn separate_id_symbol_tpm(content: &str) -> io::Result<(&str, &str, Box<[TPMValue]>)> {
     let elems: Vec<&str> = content.split_whitespace().collect();
@@ -45,10 +25,10 @@ pub fn separate_id_symbol_tpm(content: &str) -> io::Result<(&str, &str, Box<[TPM
 }
 
 fn study_dataset(file_path: &str, n_max: Option<usize>) -> io::Result<()> {
-    let decoder = decode_file(file_path)?;
+    // 1. Open the file, decoding it if it is compressed
+    let reader = open_expression_source(file_path)?;
 
     // 2. Return an iterator of the file lines
-    let reader = read_gct_file(decoder)?;
     let mut lines_iter = reader.lines();
     let _metadata: GCTMetadata = GCTMetadata::from_lines(&mut lines_iter)?;
 
@@ -92,10 +72,8 @@ fn main() -> io::Result<()> {
     study_dataset(file_path, None)?;
 
     // let file_path: &str  = "../../../data/GTEx_Analysis_v10_RNASeQCv2.4.2_gene_median_tpm.gct.gz";
-    // 1. Decode gz file
-    let decoder = decode_file(file_path)?;
-    // 2. Return an iterator of the file lines
-    let reader = read_gct_file(decoder)?;
+    // 1. Open the file, decoding gzip, zstd or xz from its magic bytes
+    let reader = open_expression_source(file_path)?;
 
     let summary_loader = GtexSummaryLoader::new(Some(5), None);
     let summary = summary_loader.load_summary(reader)?;
@@ -1,26 +1,9 @@
-use flate2::read::GzDecoder;
-use gtex_analyzer::expression_analysis::GtexSummaryLoader;
+use gtex_analyzer::expression_analysis::{
+    decode_expression_source, open_expression_source, ExpressionGate, ExpressionGates,
+    ExpressionStatus, GtexSummaryLoader, RowCountStatus,
+};
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, Cursor, Read};
-use std::path::Path;
-
-fn decode_file(file_path: &str) -> io::Result<Box<dyn BufRead>> {
-    let path = Path::new(file_path);
-    let file = File::open(path)?;
-
-    // Check if the file is a `.gz` and decode it if necessary
-    if file_path.ends_with(".gz") {
-        let decoder = GzDecoder::new(file);
-        Ok(Box::new(BufReader::new(decoder)))
-    } else {
-        Ok(Box::new(BufReader::new(file)))
-    }
-}
-
-fn read_gct_file<R: Read>(decoder: R) -> io::Result<BufReader<R>> {
-    let reader = io::BufReader::new(decoder);
-    Ok(reader)
-}
+use std::io::{self, BufReader, Cursor};
 
 #[test]
 fn test_empty_file_returns_error() {
@@ -61,10 +44,8 @@ fn test_on_sample_dataset() -> io::Result<()> {
     let file_path: &str = "data/GTEx_RNASeq_gene_median_tpm_HEAD.gct"; // bulk Tissue Expression
 
     // let file_path: &str  = "../../../data/GTEx_Analysis_v10_RNASeQCv2.4.2_gene_median_tpm.gct.gz";
-    // 1. Decode gz file
-    let decoder = decode_file(file_path)?;
-    // 2. Return an iterator of the file lines
-    let reader = read_gct_file(decoder)?;
+    // 1. Open the file, decoding it if it is compressed
+    let reader = open_expression_source(file_path)?;
 
     let summary_loader = GtexSummaryLoader::new(Some(10), None);
     let summary = summary_loader.load_summary(reader)?;
@@ -87,3 +68,90 @@ fn test_on_sample_dataset() -> io::Result<()> {
     //Add more specifict assertions
     Ok(())
 }
+
+#[test]
+fn test_sample_dataset_is_truncated() -> io::Result<()> {
+    // The sample keeps the dimension line of the full release but only its first rows
+    let reader = open_expression_source("data/GTEx_RNASeq_gene_median_tpm_HEAD.gct")?;
+    let summary = GtexSummaryLoader::new(None, None).load_summary(reader)?;
+
+    assert!(!summary.is_complete());
+    assert_eq!(
+        summary.get_row_count_status(),
+        RowCountStatus::Truncated {
+            declared: 59033,
+            found: summary.get_results().len()
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn test_sample_dataset_untestable_genes() -> io::Result<()> {
+    let reader = open_expression_source("data/GTEx_RNASeq_gene_median_tpm_HEAD.gct")?;
+    let summary = GtexSummaryLoader::new(None, None).load_summary(reader)?;
+
+    // MIR6859-1 is 0 in every tissue, DDX11L1 is expressed in only a few
+    let results = summary.get_results();
+    assert_eq!(
+        results["ENSG00000278267.1"].status,
+        ExpressionStatus::NotExpressed
+    );
+    assert_eq!(
+        results["ENSG00000223972.5"].status,
+        ExpressionStatus::Tested
+    );
+
+    let counts = summary.get_status_counts();
+    assert!(counts[&ExpressionStatus::NotExpressed] > 0);
+    assert_eq!(counts.values().sum::<usize>(), results.len());
+    for dge in results.values() {
+        assert!(dge
+            .up_regulated
+            .iter()
+            .chain(&dge.down_regulated)
+            .all(|tissue| tissue.z_score.is_finite()));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_sample_dataset_expression_gates() -> io::Result<()> {
+    let reader = open_expression_source("data/GTEx_RNASeq_gene_median_tpm_HEAD.gct")?;
+    let gates = ExpressionGates {
+        min_tpm: Some(1.0),
+        min_fold_change: Some(2.0),
+        ..Default::default()
+    };
+    let summary = GtexSummaryLoader::new(None, None)
+        .with_expression_gates(gates)
+        .load_summary(reader)?;
+
+    // DDX11L1 peaks at 0.17 TPM: its z >= 2 call is suppressed by the TPM gate
+    let ddx11l1 = &summary.get_results()["ENSG00000223972.5"];
+    assert!(ddx11l1.up_regulated.is_empty());
+    assert!(!ddx11l1.suppressed.is_empty());
+    assert!(ddx11l1
+        .suppressed
+        .iter()
+        .all(|call| call.gate == ExpressionGate::MinTpm));
+    Ok(())
+}
+
+#[test]
+fn test_load_compressed_without_extension() -> io::Result<()> {
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    let content = std::fs::read("data/GTEx_RNASeq_gene_median_tpm_HEAD.gct")?;
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&content)?;
+
+    // A portal download renamed without its `.gz` extension: only the content is known
+    let reader = decode_expression_source(Cursor::new(encoder.finish()?))?;
+    let summary_loader = GtexSummaryLoader::new(Some(10), None);
+    let summary = summary_loader.load_summary(reader)?;
+
+    assert_eq!(summary.get_results().len(), 10);
+    Ok(())
+}